@@ -0,0 +1,183 @@
+//! `keygen_vk`, `keygen_pk`, `create_proof` and `verify_proof` times of `MyCircuit` for the
+//! `TableColumn` and advice table strategies, plain and hardened, over a sweep of `k`, input
+//! counts and table sizes.
+//!
+//! Besides the criterion reports, `cargo bench --bench lookup` writes
+//! `target/criterion/lookup-summary.json`: the mean time in nanoseconds of every step of every
+//! case, with the proof size, to diff between runs. A step criterion did not run (e.g. filtered
+//! out on the command line) keeps its last measurement, or `null` if it never ran.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use criterion::{BatchSize, BenchmarkId, Criterion};
+use halo2_proofs::{
+    circuit::Value,
+    halo2curves::bn256::Fr,
+    plonk::{keygen_pk, keygen_vk},
+};
+use lookup_test::{
+    prover::{self, MultiOpen},
+    AdviceLookup, LookupTable, MyCircuit, StrategyChoice, TableColumnLookup,
+};
+use serde_json::{json, Map, Value as Json};
+
+/// The sweep, cases whose inputs or table do not fit in `2^k` are skipped
+const KS: [u32; 2] = [8, 10];
+const INPUTS: [usize; 2] = [16, 128];
+const TABLE_SIZES: [usize; 2] = [16, 128];
+
+const STEPS: [&str; 4] = ["keygen_vk", "keygen_pk", "create_proof", "verify_proof"];
+
+/// One benchmarked circuit, the criterion group and id its steps are stored under
+struct Case {
+    group: String,
+    id: String,
+    k: u32,
+    inputs: usize,
+    table_size: usize,
+    proof_size: usize,
+}
+
+/// `inputs` values cycling through `table`, so every proof verifies
+fn cycling_circuit<S: StrategyChoice>(inputs: usize, table: &LookupTable<Fr>) -> MyCircuit<Fr, S> {
+    let a = (0..inputs)
+        .map(|i| Value::known(Fr::from((i % table.len()) as u64 + 1)))
+        .collect();
+    MyCircuit::with_table(a, table.clone())
+}
+
+fn bench_strategy<S: StrategyChoice>(c: &mut Criterion, cases: &mut Vec<Case>) {
+    let group_name = if S::HARDENED {
+        format!("{:?}-hardened", S::STRATEGY)
+    } else {
+        format!("{:?}", S::STRATEGY)
+    };
+    let mut group = c.benchmark_group(&group_name);
+    group.sample_size(10);
+
+    for k in KS {
+        let params = prover::setup(k);
+        for inputs in INPUTS {
+            for table_size in TABLE_SIZES {
+                let table = LookupTable::from_fn(table_size, |i| vec![Fr::from(i as u64 + 1)]);
+                let circuit = cycling_circuit::<S>(inputs, &table);
+                if circuit.check_size(k).is_err() {
+                    continue;
+                }
+                let instances = circuit.instances();
+                let instance_refs = instances.iter().map(|column| column.as_slice()).collect::<Vec<_>>();
+
+                let vk = keygen_vk(&params, &circuit).unwrap();
+                let pk = keygen_pk(&params, vk.clone(), &circuit).unwrap();
+                let proof = prover::prove(
+                    &params,
+                    &pk,
+                    cycling_circuit::<S>(inputs, &table),
+                    &instance_refs,
+                    MultiOpen::Shplonk,
+                )
+                .unwrap();
+
+                let id = format!("k{}-n{}-t{}", k, inputs, table_size);
+                group.bench_function(BenchmarkId::new(STEPS[0], &id), |b| {
+                    b.iter(|| keygen_vk(&params, &circuit).unwrap())
+                });
+                group.bench_function(BenchmarkId::new(STEPS[1], &id), |b| {
+                    b.iter_batched(
+                        || vk.clone(),
+                        |vk| keygen_pk(&params, vk, &circuit).unwrap(),
+                        BatchSize::SmallInput,
+                    )
+                });
+                group.bench_function(BenchmarkId::new(STEPS[2], &id), |b| {
+                    b.iter_batched(
+                        || cycling_circuit::<S>(inputs, &table),
+                        |circuit| prover::prove(&params, &pk, circuit, &instance_refs, MultiOpen::Shplonk).unwrap(),
+                        BatchSize::SmallInput,
+                    )
+                });
+                group.bench_function(BenchmarkId::new(STEPS[3], &id), |b| {
+                    b.iter(|| {
+                        prover::verify(&params, pk.get_vk(), &proof, &instance_refs, MultiOpen::Shplonk).unwrap()
+                    })
+                });
+
+                cases.push(Case {
+                    group: group_name.clone(),
+                    id,
+                    k,
+                    inputs,
+                    table_size,
+                    proof_size: proof.len(),
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+/// Where criterion keeps its measurements
+fn criterion_dir() -> PathBuf {
+    match env::var_os("CRITERION_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("target"))
+            .join("criterion"),
+    }
+}
+
+/// Mean of the last measurement of `step`, `null` when there is none
+fn mean_ns(dir: &Path, case: &Case, step: &str) -> Json {
+    let estimates = dir.join(&case.group).join(step).join(&case.id).join("new").join("estimates.json");
+    fs::read_to_string(estimates)
+        .ok()
+        .and_then(|estimates| serde_json::from_str::<Json>(&estimates).ok())
+        .map(|estimates| estimates["mean"]["point_estimate"].clone())
+        .unwrap_or(Json::Null)
+}
+
+fn write_summary(cases: &[Case]) -> io::Result<PathBuf> {
+    let dir = criterion_dir();
+    let summary = cases
+        .iter()
+        .map(|case| {
+            let mean_ns = STEPS
+                .iter()
+                .map(|step| (step.to_string(), mean_ns(&dir, case, step)))
+                .collect::<Map<_, _>>();
+            json!({
+                "strategy": case.group,
+                "k": case.k,
+                "inputs": case.inputs,
+                "table_size": case.table_size,
+                "proof_size": case.proof_size,
+                "mean_ns": mean_ns,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("lookup-summary.json");
+    fs::write(&path, serde_json::to_string_pretty(&summary)? + "\n")?;
+    Ok(path)
+}
+
+fn main() {
+    let mut c = Criterion::default().configure_from_args();
+    let mut cases = vec![];
+
+    bench_strategy::<TableColumnLookup>(&mut c, &mut cases);
+    bench_strategy::<TableColumnLookup<true>>(&mut c, &mut cases);
+    bench_strategy::<AdviceLookup>(&mut c, &mut cases);
+    bench_strategy::<AdviceLookup<true>>(&mut c, &mut cases);
+
+    c.final_summary();
+    match write_summary(&cases) {
+        Ok(path) => println!("summary written to {}", path.display()),
+        Err(e) => eprintln!("could not write the summary: {}", e),
+    }
+}
@@ -0,0 +1,441 @@
+//! KZG params, verifying keys and proofs on disk.
+//!
+//! Every file starts with a header: the artifact kind and its encoding, then the circuit it was
+//! made for (circuit id, `k`, lookup strategy and a hash of the table). Loading checks the
+//! header against the circuit at hand, so the vk or proof of another circuit fails with the
+//! field that differs instead of a failed verification. Params only depend on `k`, any circuit
+//! of that size can load them.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use ff::PrimeField;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::VerifyingKey,
+    poly::kzg::commitment::ParamsKZG,
+    SerdeFormat,
+};
+
+use crate::{
+    lookup_padding::{LookupStrategy, LookupTable, MyCircuit, StrategyChoice},
+    prover::MultiOpen,
+};
+
+const MAGIC: &[u8; 4] = b"LKUP";
+const VERSION: u8 = 1;
+// magic, version, kind, encoding, k, strategy, hardened, table hash, circuit id length
+const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 4 + 1 + 1 + 32 + 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactKind {
+    Params,
+    VerifyingKey,
+    Proof,
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArtifactKind::Params => "params",
+            ArtifactKind::VerifyingKey => "verifying key",
+            ArtifactKind::Proof => "proof",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ArtifactError {
+    Io(io::Error),
+    /// Not an artifact of this crate, or one written by another version
+    Corrupt(PathBuf),
+    WrongKind {
+        path: PathBuf,
+        expected: ArtifactKind,
+        found: ArtifactKind,
+    },
+    /// The artifact was made for another circuit, `field` names the first difference
+    Mismatch {
+        path: PathBuf,
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Io(e) => write!(f, "{}", e),
+            ArtifactError::Corrupt(path) => {
+                write!(f, "{} is not an artifact of this version of lookup_test", path.display())
+            }
+            ArtifactError::WrongKind { path, expected, found } => {
+                write!(f, "{} holds a {}, expected a {}", path.display(), found, expected)
+            }
+            ArtifactError::Mismatch {
+                path,
+                field,
+                expected,
+                found,
+            } => write!(f, "{} was made for {} {}, expected {}", path.display(), field, found, expected),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+impl From<io::Error> for ArtifactError {
+    fn from(e: io::Error) -> Self {
+        ArtifactError::Io(e)
+    }
+}
+
+fn mismatch(path: &Path, field: &'static str, expected: impl fmt::Display, found: impl fmt::Display) -> ArtifactError {
+    ArtifactError::Mismatch {
+        path: path.to_path_buf(),
+        field,
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+/// The circuit an artifact was made for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtifactHeader {
+    pub circuit_id: String,
+    pub k: u32,
+    pub strategy: LookupStrategy,
+    pub hardened: bool,
+    pub table_hash: [u8; 32],
+}
+
+impl ArtifactHeader {
+    pub fn new<S: StrategyChoice>(circuit: &MyCircuit<Fr, S>, k: u32) -> Self {
+        ArtifactHeader {
+            // the shape of `MyCircuit`, and so its vk, depends on the number of inputs
+            circuit_id: format!("MyCircuit({} inputs)", circuit.inputs()),
+            k,
+            strategy: S::STRATEGY,
+            hardened: S::HARDENED,
+            table_hash: table_hash(circuit.table()),
+        }
+    }
+
+    fn strategy_name(&self) -> String {
+        if self.hardened {
+            format!("{:?} (hardened)", self.strategy)
+        } else {
+            format!("{:?}", self.strategy)
+        }
+    }
+
+    fn write(&self, writer: &mut impl Write, kind: ArtifactKind, encoding: u8) -> io::Result<()> {
+        let strategy = match self.strategy {
+            LookupStrategy::TableColumn => 0u8,
+            LookupStrategy::Advice => 1,
+            LookupStrategy::Fixed => 2,
+            LookupStrategy::Instance => 3,
+            LookupStrategy::InstanceCopy => 4,
+        };
+        let circuit_id = self.circuit_id.as_bytes();
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, kind as u8, encoding])?;
+        writer.write_all(&self.k.to_le_bytes())?;
+        writer.write_all(&[strategy, self.hardened as u8])?;
+        writer.write_all(&self.table_hash)?;
+        writer.write_all(&(circuit_id.len() as u16).to_le_bytes())?;
+        writer.write_all(circuit_id)
+    }
+
+    /// Reads the header of `path`, returns it with the artifact kind and encoding
+    fn read(reader: &mut impl Read, path: &Path) -> Result<(Self, ArtifactKind, u8), ArtifactError> {
+        let corrupt = || ArtifactError::Corrupt(path.to_path_buf());
+        let mut read_exact = |buf: &mut [u8]| {
+            reader.read_exact(buf).map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => corrupt(),
+                _ => e.into(),
+            })
+        };
+
+        let mut bytes = [0u8; HEADER_LEN];
+        read_exact(&mut bytes)?;
+        if &bytes[..4] != MAGIC || bytes[4] != VERSION {
+            return Err(corrupt());
+        }
+        let kind = match bytes[5] {
+            0 => ArtifactKind::Params,
+            1 => ArtifactKind::VerifyingKey,
+            2 => ArtifactKind::Proof,
+            _ => return Err(corrupt()),
+        };
+        let strategy = match bytes[11] {
+            0 => LookupStrategy::TableColumn,
+            1 => LookupStrategy::Advice,
+            2 => LookupStrategy::Fixed,
+            3 => LookupStrategy::Instance,
+            4 => LookupStrategy::InstanceCopy,
+            _ => return Err(corrupt()),
+        };
+        let hardened = match bytes[12] {
+            0 => false,
+            1 => true,
+            _ => return Err(corrupt()),
+        };
+        let mut table_hash = [0u8; 32];
+        table_hash.copy_from_slice(&bytes[13..45]);
+        let mut circuit_id = vec![0u8; u16::from_le_bytes([bytes[45], bytes[46]]) as usize];
+        read_exact(&mut circuit_id)?;
+
+        let header = ArtifactHeader {
+            circuit_id: String::from_utf8(circuit_id).map_err(|_| corrupt())?,
+            k: u32::from_le_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]),
+            strategy,
+            hardened,
+            table_hash,
+        };
+        Ok((header, kind, bytes[6]))
+    }
+
+    /// Fails on the first field where `found`, read from `path`, differs from `self`
+    fn check(&self, found: &Self, path: &Path) -> Result<(), ArtifactError> {
+        if found.circuit_id != self.circuit_id {
+            return Err(mismatch(path, "circuit", &self.circuit_id, &found.circuit_id));
+        }
+        if found.k != self.k {
+            return Err(mismatch(path, "k", self.k, found.k));
+        }
+        if (found.strategy, found.hardened) != (self.strategy, self.hardened) {
+            return Err(mismatch(path, "strategy", self.strategy_name(), found.strategy_name()));
+        }
+        if found.table_hash != self.table_hash {
+            return Err(mismatch(path, "table hash", hex(&self.table_hash), hex(&found.table_hash)));
+        }
+        Ok(())
+    }
+}
+
+/// Blake2b-256 of the width, length and rows of `table`
+pub fn table_hash<F: FieldExt>(table: &LookupTable<F>) -> [u8; 32] {
+    let mut state = blake2b_simd::Params::new().hash_length(32).to_state();
+    state.update(&(table.width() as u64).to_le_bytes());
+    state.update(&(table.len() as u64).to_le_bytes());
+    for value in table.rows().flatten() {
+        state.update(value.to_repr().as_ref());
+    }
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(state.finalize().as_bytes());
+    hash
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn format_byte(format: SerdeFormat) -> u8 {
+    match format {
+        SerdeFormat::Processed => 0,
+        SerdeFormat::RawBytes => 1,
+        SerdeFormat::RawBytesUnchecked => 2,
+    }
+}
+
+fn read_format(encoding: u8, path: &Path) -> Result<SerdeFormat, ArtifactError> {
+    match encoding {
+        0 => Ok(SerdeFormat::Processed),
+        1 => Ok(SerdeFormat::RawBytes),
+        2 => Ok(SerdeFormat::RawBytesUnchecked),
+        _ => Err(ArtifactError::Corrupt(path.to_path_buf())),
+    }
+}
+
+fn create(path: &Path, header: &ArtifactHeader, kind: ArtifactKind, encoding: u8) -> io::Result<BufWriter<File>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    header.write(&mut writer, kind, encoding)?;
+    Ok(writer)
+}
+
+/// Opens `path` and checks it holds a `kind`, returns the reader positioned after the header
+fn open(path: &Path, kind: ArtifactKind) -> Result<(BufReader<File>, ArtifactHeader, u8), ArtifactError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (header, found, encoding) = ArtifactHeader::read(&mut reader, path)?;
+    if found != kind {
+        return Err(ArtifactError::WrongKind {
+            path: path.to_path_buf(),
+            expected: kind,
+            found,
+        });
+    }
+    Ok((reader, header, encoding))
+}
+
+pub fn save_params(
+    path: &Path,
+    header: &ArtifactHeader,
+    params: &ParamsKZG<Bn256>,
+    format: SerdeFormat,
+) -> io::Result<()> {
+    let mut writer = create(path, header, ArtifactKind::Params, format_byte(format))?;
+    params.write_custom(&mut writer, format)?;
+    writer.flush()
+}
+
+/// Loads params for circuits of `2^k` rows, whatever circuit they were made for
+pub fn load_params(path: &Path, k: u32) -> Result<ParamsKZG<Bn256>, ArtifactError> {
+    let (mut reader, found, encoding) = open(path, ArtifactKind::Params)?;
+    if found.k != k {
+        return Err(mismatch(path, "k", k, found.k));
+    }
+    Ok(ParamsKZG::read_custom(&mut reader, read_format(encoding, path)?)?)
+}
+
+pub fn save_vk(
+    path: &Path,
+    header: &ArtifactHeader,
+    vk: &VerifyingKey<G1Affine>,
+    format: SerdeFormat,
+) -> io::Result<()> {
+    let mut writer = create(path, header, ArtifactKind::VerifyingKey, format_byte(format))?;
+    vk.write(&mut writer, format)?;
+    writer.flush()
+}
+
+/// Loads the vk of the `MyCircuit` `expected` describes
+pub fn load_vk<S: StrategyChoice>(
+    path: &Path,
+    expected: &ArtifactHeader,
+) -> Result<VerifyingKey<G1Affine>, ArtifactError> {
+    let (mut reader, found, encoding) = open(path, ArtifactKind::VerifyingKey)?;
+    expected.check(&found, path)?;
+    Ok(VerifyingKey::read::<_, MyCircuit<Fr, S>>(&mut reader, read_format(encoding, path)?)?)
+}
+
+pub fn save_proof(path: &Path, header: &ArtifactHeader, proof: &[u8], scheme: MultiOpen) -> io::Result<()> {
+    let mut writer = create(path, header, ArtifactKind::Proof, scheme as u8)?;
+    writer.write_all(proof)?;
+    writer.flush()
+}
+
+/// Loads a proof of the circuit `expected` describes, created with `scheme`
+pub fn load_proof(path: &Path, expected: &ArtifactHeader, scheme: MultiOpen) -> Result<Vec<u8>, ArtifactError> {
+    let (mut reader, found, encoding) = open(path, ArtifactKind::Proof)?;
+    expected.check(&found, path)?;
+    let found_scheme = match encoding {
+        0 => MultiOpen::Shplonk,
+        1 => MultiOpen::Gwc,
+        _ => return Err(ArtifactError::Corrupt(path.to_path_buf())),
+    };
+    if found_scheme != scheme {
+        return Err(mismatch(path, "multi-open scheme", format!("{:?}", scheme), format!("{:?}", found_scheme)));
+    }
+    let mut proof = vec![];
+    reader.read_to_end(&mut proof)?;
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use assert_matches::assert_matches;
+    use halo2_proofs::{circuit::Value, plonk::Circuit};
+
+    use super::*;
+    use crate::{
+        lookup_padding::{AdviceLookup, FixedLookup},
+        prover::{keygen, prove, setup, verify},
+    };
+
+    fn witness(a: &[u64]) -> Vec<Value<Fr>> {
+        a.iter().map(|v| Value::known(Fr::from(*v))).collect()
+    }
+
+    /// A fresh directory for the artifacts of one test
+    fn artifact_dir(test: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("lookup_test-{}-{}", test, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_artifacts_round_trip() {
+        let dir = artifact_dir("round_trip");
+        let (params_path, vk_path, proof_path) = (dir.join("params.bin"), dir.join("vk.bin"), dir.join("proof.bin"));
+        let k = 5;
+        let circuit = MyCircuit::<Fr, AdviceLookup<true>>::new(witness(&[1, 2, 9]));
+        let header = ArtifactHeader::new(&circuit, k);
+        let params = setup(k);
+        let pk = keygen(&params, &circuit.without_witnesses()).unwrap();
+        let proof = prove(&params, &pk, circuit, &[], MultiOpen::Gwc).unwrap();
+        save_proof(&proof_path, &header, &proof, MultiOpen::Gwc).unwrap();
+
+        for format in [SerdeFormat::RawBytes, SerdeFormat::Processed] {
+            save_params(&params_path, &header, &params, format).unwrap();
+            save_vk(&vk_path, &header, pk.get_vk(), format).unwrap();
+
+            let params = load_params(&params_path, k).unwrap();
+            let vk = load_vk::<AdviceLookup<true>>(&vk_path, &header).unwrap();
+            let proof = load_proof(&proof_path, &header, MultiOpen::Gwc).unwrap();
+            verify(&params, &vk, &proof, &[], MultiOpen::Gwc).unwrap();
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mismatched_artifacts() {
+        let dir = artifact_dir("mismatch");
+        let (params_path, vk_path, proof_path) = (dir.join("params.bin"), dir.join("vk.bin"), dir.join("proof.bin"));
+        let k = 5;
+        let circuit = MyCircuit::<Fr, AdviceLookup<true>>::new(witness(&[1, 2, 9]));
+        let header = ArtifactHeader::new(&circuit, k);
+        let params = setup(k);
+        let pk = keygen(&params, &circuit).unwrap();
+        save_params(&params_path, &header, &params, SerdeFormat::RawBytes).unwrap();
+        save_vk(&vk_path, &header, pk.get_vk(), SerdeFormat::RawBytes).unwrap();
+        // the header is checked before the proof bytes are read
+        save_proof(&proof_path, &header, &[0u8; 32], MultiOpen::Shplonk).unwrap();
+
+        let other_table = LookupTable::from_values((1..11u64).map(Fr::from));
+        let other_table = MyCircuit::<Fr, AdviceLookup<true>>::with_table(witness(&[1, 2, 9]), other_table);
+        assert_matches!(
+            load_vk::<AdviceLookup<true>>(&vk_path, &ArtifactHeader::new(&other_table, k)),
+            Err(ArtifactError::Mismatch { field: "table hash", .. })
+        );
+        let other_inputs = MyCircuit::<Fr, AdviceLookup<true>>::new(witness(&[1, 2, 9, 9]));
+        assert_matches!(
+            load_vk::<AdviceLookup<true>>(&vk_path, &ArtifactHeader::new(&other_inputs, k)),
+            Err(ArtifactError::Mismatch { field: "circuit", .. })
+        );
+        let other_strategy = MyCircuit::<Fr, FixedLookup<true>>::new(witness(&[1, 2, 9]));
+        assert_matches!(
+            load_proof(&proof_path, &ArtifactHeader::new(&other_strategy, k), MultiOpen::Shplonk),
+            Err(ArtifactError::Mismatch { field: "strategy", .. })
+        );
+
+        let error = load_vk::<AdviceLookup<true>>(&vk_path, &ArtifactHeader::new(&circuit, 6)).unwrap_err();
+        assert_eq!(error.to_string(), format!("{} was made for k 5, expected 6", vk_path.display()));
+        assert_matches!(load_params(&params_path, 6), Err(ArtifactError::Mismatch { field: "k", .. }));
+        assert_matches!(
+            load_proof(&proof_path, &header, MultiOpen::Gwc),
+            Err(ArtifactError::Mismatch { field: "multi-open scheme", .. })
+        );
+
+        assert_matches!(
+            load_proof(&vk_path, &header, MultiOpen::Shplonk),
+            Err(ArtifactError::WrongKind {
+                expected: ArtifactKind::Proof,
+                found: ArtifactKind::VerifyingKey,
+                ..
+            })
+        );
+        let raw = dir.join("raw.bin");
+        fs::write(&raw, [0u8; 8]).unwrap();
+        assert_matches!(load_params(&raw, k), Err(ArtifactError::Corrupt(_)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
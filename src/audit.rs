@@ -0,0 +1,370 @@
+//! Padding-soundness audit for lookup arguments.
+//!
+//! The prover fills every advice cell the circuit never assigned with zero, so a table
+//! expression evaluated on such a row contributes a tuple that no assignment put there
+//! (see `test_lookup_on_different_rows`). The audit synthesizes a circuit into a
+//! `Recorder`, which keeps track of the cells that were actually assigned, and reports
+//! for each lookup the table tuples that only come from unassigned rows, none when it is clean.
+
+use std::{collections::HashSet, fmt};
+
+use ff::PrimeField;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Value,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error,
+        Expression, FloorPlanner, Fixed, Instance, Selector,
+    },
+};
+
+/// A column queried by the table side of a lookup
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueriedColumn {
+    Advice(usize),
+    Fixed(usize),
+    Instance(usize),
+}
+
+impl fmt::Display for QueriedColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueriedColumn::Advice(index) => write!(f, "advice[{}]", index),
+            QueriedColumn::Fixed(index) => write!(f, "fixed[{}]", index),
+            QueriedColumn::Instance(index) => write!(f, "instance[{}]", index),
+        }
+    }
+}
+
+/// Table tuples of one lookup that only unassigned rows contribute, empty when the padding
+/// adds nothing
+#[derive(Clone, Debug)]
+pub struct PaddingReport<F: FieldExt> {
+    pub lookup: String,
+    pub columns: Vec<QueriedColumn>,
+    pub implicit_values: Vec<Vec<F>>,
+}
+
+impl<F: FieldExt> fmt::Display for PaddingReport<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let columns = self.columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        if self.implicit_values.is_empty() {
+            return writeln!(
+                f,
+                "lookup \"{}\" over [{}] gains no tuples from the padding",
+                self.lookup,
+                columns.join(", ")
+            );
+        }
+        writeln!(
+            f,
+            "lookup \"{}\" over [{}] accepts tuples only the padding puts in the table:",
+            self.lookup,
+            columns.join(", ")
+        )?;
+        for tuple in self.implicit_values.iter() {
+            writeln!(f, "    {:?}", tuple)?;
+        }
+        Ok(())
+    }
+}
+
+/// Synthesizes `circuit` with `2^k` rows and audits every lookup whose table side queries
+/// advice or instance columns, instance rows past `instance` are padding. Every such lookup
+/// gets a report, with no implicit values when it is clean.
+pub fn audit_lookups<F: FieldExt, C: Circuit<F>>(
+    k: u32,
+    circuit: &C,
+    instance: Vec<Vec<F>>,
+) -> Result<Vec<PaddingReport<F>>, Error> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+
+    let n = 1usize << k;
+    let usable_rows = n.saturating_sub(cs.blinding_factors() + 1);
+    let mut recorder = Recorder {
+        k,
+        usable_rows,
+        advice: vec![vec![None; n]; cs.num_advice_columns()],
+        fixed: vec![vec![None; n]; cs.num_fixed_columns()],
+        selectors: vec![vec![false; n]; cs.num_selectors()],
+        instance,
+    };
+    C::FloorPlanner::synthesize(&mut recorder, circuit, config, cs.constants().clone())?;
+
+    let mut reports = vec![];
+    for argument in cs.lookups().iter() {
+        let mut columns = vec![];
+        for expression in argument.table_expressions().iter() {
+            for column in queried_columns(expression) {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        if !columns
+            .iter()
+            .any(|c| matches!(c, QueriedColumn::Advice(_) | QueriedColumn::Instance(_)))
+        {
+            continue;
+        }
+
+        let mut explicit = HashSet::new();
+        let mut implicit = vec![];
+        for row in 0..usable_rows {
+            let evaluated = argument
+                .table_expressions()
+                .iter()
+                .map(|expression| recorder.evaluate(expression, row))
+                .collect::<Vec<_>>();
+            let tuple = evaluated.iter().map(|(value, _)| *value).collect::<Vec<_>>();
+            if evaluated.iter().any(|(_, unassigned)| *unassigned) {
+                implicit.push(tuple);
+            } else {
+                explicit.insert(tuple_key(&tuple));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let implicit_values = implicit
+            .into_iter()
+            .filter(|tuple| {
+                let key = tuple_key(tuple);
+                !explicit.contains(&key) && seen.insert(key)
+            })
+            .collect::<Vec<_>>();
+        reports.push(PaddingReport {
+            lookup: argument.name().to_string(),
+            columns,
+            implicit_values,
+        });
+    }
+    Ok(reports)
+}
+
+fn tuple_key<F: FieldExt>(tuple: &[F]) -> Vec<u8> {
+    tuple.iter().flat_map(|v| v.to_repr().as_ref().to_vec()).collect()
+}
+
+fn queried_columns<F: FieldExt>(expression: &Expression<F>) -> Vec<QueriedColumn> {
+    expression.evaluate(
+        &|_| vec![],
+        &|_| vec![],
+        &|query| vec![QueriedColumn::Fixed(query.column_index())],
+        &|query| vec![QueriedColumn::Advice(query.column_index())],
+        &|query| vec![QueriedColumn::Instance(query.column_index())],
+        &|_| vec![],
+        &|a| a,
+        &|mut a, b| {
+            a.extend(b);
+            a
+        },
+        &|mut a, b| {
+            a.extend(b);
+            a
+        },
+        &|a, _| a,
+    )
+}
+
+/// Records which cells a circuit assigns, unassigned cells read as zero like in the prover
+struct Recorder<F: FieldExt> {
+    k: u32,
+    usable_rows: usize,
+    advice: Vec<Vec<Option<F>>>,
+    fixed: Vec<Vec<Option<F>>>,
+    selectors: Vec<Vec<bool>>,
+    instance: Vec<Vec<F>>,
+}
+
+impl<F: FieldExt> Recorder<F> {
+    fn check_row(&self, row: usize) -> Result<(), Error> {
+        if row >= self.usable_rows {
+            return Err(Error::NotEnoughRowsAvailable { current_k: self.k });
+        }
+        Ok(())
+    }
+
+    fn rotated(&self, row: usize, rotation: i32) -> usize {
+        let n = 1i64 << self.k;
+        ((row as i64 + rotation as i64).rem_euclid(n)) as usize
+    }
+
+    /// Evaluates `expression` at `row`, the flag is set when the value depends on an
+    /// unassigned cell. A product with an assigned zero (e.g. a disabled selector)
+    /// does not depend on the other factor, so the flag is cleared.
+    fn evaluate(&self, expression: &Expression<F>, row: usize) -> (F, bool) {
+        let cell = |value: Option<F>| (value.unwrap_or_else(F::zero), value.is_none());
+        expression.evaluate(
+            &|constant| (constant, false),
+            &|selector: Selector| (F::from(self.selectors[selector.index()][row] as u64), false),
+            &|query| {
+                let row = self.rotated(row, query.rotation().0);
+                cell(self.fixed[query.column_index()][row])
+            },
+            &|query| {
+                let row = self.rotated(row, query.rotation().0);
+                cell(self.advice[query.column_index()][row])
+            },
+            &|query| {
+                let row = self.rotated(row, query.rotation().0);
+                cell(self.instance[query.column_index()].get(row).copied())
+            },
+            // phase-2 challenges are not known outside the prover
+            &|_| (F::zero(), false),
+            &|(a, unassigned)| (-a, unassigned),
+            &|(a, ua), (b, ub)| (a + b, ua || ub),
+            &|(a, ua), (b, ub)| {
+                let gated = (!ua && a == F::zero()) || (!ub && b == F::zero());
+                (a * b, (ua || ub) && !gated)
+            },
+            &|(a, unassigned), scalar| (a * scalar, unassigned),
+        )
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for Recorder<F> {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn annotate_column<A, AR>(&mut self, _: A, _: Column<Any>)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+    }
+
+    fn enable_selector<A, AR>(&mut self, _: A, selector: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        self.selectors[selector.index()][row] = true;
+        Ok(())
+    }
+
+    fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
+        self.check_row(row)?;
+        self.instance
+            .get(column.index())
+            .and_then(|column| column.get(row))
+            .map(|v| Value::known(*v))
+            .ok_or(Error::BoundsFailure)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Advice>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        let mut value = None;
+        to().map(|v| value = Some(v.into().evaluate()));
+        // an unknown witness still occupies the cell, it is not padding
+        self.advice[column.index()][row] = Some(value.unwrap_or_else(F::zero));
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Fixed>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.check_row(row)?;
+        let mut value = None;
+        to().map(|v| value = Some(v.into().evaluate()));
+        self.fixed[column.index()][row] = Some(value.unwrap_or_else(F::zero));
+        Ok(())
+    }
+
+    fn copy(&mut self, _: Column<Any>, _: usize, _: Column<Any>, _: usize) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        column: Column<Fixed>,
+        from_row: usize,
+        to: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        self.check_row(from_row)?;
+        let mut value = None;
+        to.map(|v| value = Some(v.evaluate()));
+        for row in from_row..self.usable_rows {
+            self.fixed[column.index()][row] = Some(value.unwrap_or_else(F::zero));
+        }
+        Ok(())
+    }
+
+    fn get_challenge(&self, _: Challenge) -> Value<F> {
+        Value::unknown()
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::halo2curves::bn256::Fr as Fp;
+
+    use super::*;
+    use crate::lookup_padding::{AdviceLookup, FixedLookup, InstanceLookup, MyCircuit};
+
+    #[test]
+    fn test_audit_reports_zero_padding() {
+        let a = [0, 1, 2, 3].map(|v| Value::known(Fp::from(v))).to_vec();
+
+        let reports = audit_lookups(5, &MyCircuit::<Fp>::new(a.clone()), vec![]).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].lookup, "lookup_any");
+        assert_eq!(reports[0].implicit_values, vec![vec![Fp::zero()]]);
+        assert!(matches!(reports[0].columns[..], [QueriedColumn::Advice(_)]));
+
+        // the hardened table gates the padding with `q_t`, nothing is added implicitly
+        let reports = audit_lookups(5, &MyCircuit::<Fp, AdviceLookup<true>>::new(a.clone()), vec![]).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].lookup, "lookup_any");
+        assert!(reports[0].implicit_values.is_empty());
+
+        // fixed tables are not audited
+        let reports = audit_lookups(5, &MyCircuit::<Fp, FixedLookup>::new(a.clone()), vec![]).unwrap();
+        assert!(reports.is_empty());
+
+        // a public table is zero past the public inputs
+        let circuit = MyCircuit::<Fp, InstanceLookup>::new(a);
+        let reports = audit_lookups(5, &circuit, circuit.instances()).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].lookup, "lookup_instance");
+        assert_eq!(reports[0].implicit_values, vec![vec![Fp::zero()]]);
+    }
+}
@@ -0,0 +1,375 @@
+//! Bitwise operations proved with tuple lookups into `(op, x, y, x op y)` tables.
+//!
+//! All operations share one physical table, told apart by the `op` tag. Row 0 is the
+//! all-zero tuple, which disabled rows look up. Operands are split into `b`-bit chunks
+//! (bytes for `b = 8`) with a running sum per operand and result:
+//!
+//! ```text
+//! acc_i = chunk_i + 2^b * acc_{i+1},  acc_0 = value,  acc_n = 0
+//! ```
+//!
+//! and each row of chunks `(op, x_i, y_i, z_i)` is looked up in the table. The lookup also
+//! range checks the chunks, so the result `acc_0` of `z` is `x op y` on `n * b` bits.
+
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+use crate::range_check::decompose;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BitwiseOp {
+    Xor,
+    And,
+    Or,
+    /// Unary, `y` is 0 in its table rows
+    Not,
+}
+
+impl BitwiseOp {
+    pub const ALL: [BitwiseOp; 4] = [BitwiseOp::Xor, BitwiseOp::And, BitwiseOp::Or, BitwiseOp::Not];
+
+    /// Tag of the operation's table rows, 0 is left for the all-zero row
+    fn tag(&self) -> u64 {
+        match self {
+            BitwiseOp::Xor => 1,
+            BitwiseOp::And => 2,
+            BitwiseOp::Or => 3,
+            BitwiseOp::Not => 4,
+        }
+    }
+
+    fn is_unary(&self) -> bool {
+        *self == BitwiseOp::Not
+    }
+
+    /// `x op y` on `bits`-bit chunks
+    fn apply(&self, x: u64, y: u64, bits: usize) -> u64 {
+        let mask = (1 << bits) - 1;
+        match self {
+            BitwiseOp::Xor => x ^ y,
+            BitwiseOp::And => x & y,
+            BitwiseOp::Or => x | y,
+            BitwiseOp::Not => !x & mask,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BitwiseConfig {
+    // chunks of the operands and of the result
+    x: Column<Advice>,
+    y: Column<Advice>,
+    z: Column<Advice>,
+    // running sums of the chunks, row 0 holds the full values
+    acc_x: Column<Advice>,
+    acc_y: Column<Advice>,
+    acc_z: Column<Advice>,
+    op: Column<Fixed>,
+    // enabled on the chunk rows: running sum step and table lookup
+    q: Selector,
+    // enabled on the row below the last chunk: the running sums end at 0
+    q_end: Selector,
+    t_op: TableColumn,
+    t_x: TableColumn,
+    t_y: TableColumn,
+    t_z: TableColumn,
+    bits: usize,
+}
+
+pub struct BitwiseChip<F: FieldExt> {
+    config: BitwiseConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> BitwiseChip<F> {
+    pub fn construct(config: BitwiseConfig) -> Self {
+        BitwiseChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `bits` is the chunk width, the table has `1 + 3 * 2^(2 * bits) + 2^bits` rows
+    pub fn configure(meta: &mut ConstraintSystem<F>, bits: usize) -> BitwiseConfig {
+        assert!(bits > 0 && bits <= 8, "chunk width must be in 1..=8 bits");
+
+        let x = meta.advice_column();
+        let y = meta.advice_column();
+        let z = meta.advice_column();
+        let acc_x = meta.advice_column();
+        let acc_y = meta.advice_column();
+        let acc_z = meta.advice_column();
+        let op = meta.fixed_column();
+        let q = meta.complex_selector();
+        let q_end = meta.selector();
+        let t_op = meta.lookup_table_column();
+        let t_x = meta.lookup_table_column();
+        let t_y = meta.lookup_table_column();
+        let t_z = meta.lookup_table_column();
+
+        for acc in [acc_x, acc_y, acc_z] {
+            meta.enable_equality(acc);
+        }
+
+        let two_pow_b = Expression::Constant(F::from(1 << bits));
+
+        meta.create_gate("bitwise running sum", |meta| {
+            let q = meta.query_selector(q);
+            [(acc_x, x), (acc_y, y), (acc_z, z)]
+                .into_iter()
+                .map(|(acc, chunk)| {
+                    let acc_cur = meta.query_advice(acc, Rotation::cur());
+                    let acc_next = meta.query_advice(acc, Rotation::next());
+                    let chunk = meta.query_advice(chunk, Rotation::cur());
+                    q.clone() * (acc_cur - chunk - two_pow_b.clone() * acc_next)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        meta.create_gate("bitwise running sum end", |meta| {
+            let q_end = meta.query_selector(q_end);
+            [acc_x, acc_y, acc_z]
+                .into_iter()
+                .map(|acc| q_end.clone() * meta.query_advice(acc, Rotation::cur()))
+                .collect::<Vec<_>>()
+        });
+
+        // disabled rows look up (0, 0, 0, 0), the first row of the table
+        meta.lookup("bitwise", |meta| {
+            let q = meta.query_selector(q);
+            let op = meta.query_fixed(op, Rotation::cur());
+            let x = meta.query_advice(x, Rotation::cur());
+            let y = meta.query_advice(y, Rotation::cur());
+            let z = meta.query_advice(z, Rotation::cur());
+            vec![
+                (q.clone() * op, t_op),
+                (q.clone() * x, t_x),
+                (q.clone() * y, t_y),
+                (q * z, t_z),
+            ]
+        });
+
+        BitwiseConfig {
+            x,
+            y,
+            z,
+            acc_x,
+            acc_y,
+            acc_z,
+            op,
+            q,
+            q_end,
+            t_op,
+            t_x,
+            t_y,
+            t_z,
+            bits,
+        }
+    }
+
+    /// Loads the all-zero row and the rows of every `BitwiseOp`, once per circuit
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let bits = self.config.bits;
+        let size = 1u64 << bits;
+
+        let mut rows = vec![(0, 0, 0, 0)];
+        for op in BitwiseOp::ALL {
+            let ys = if op.is_unary() { 0..1 } else { 0..size };
+            for x in 0..size {
+                for y in ys.clone() {
+                    rows.push((op.tag(), x, y, op.apply(x, y, bits)));
+                }
+            }
+        }
+
+        layouter.assign_table(
+            || "bitwise table",
+            |mut table| {
+                for (i, (op, x, y, z)) in rows.iter().enumerate() {
+                    let columns = [
+                        (self.config.t_op, op),
+                        (self.config.t_x, x),
+                        (self.config.t_y, y),
+                        (self.config.t_z, z),
+                    ];
+                    for (column, value) in columns {
+                        table.assign_cell(|| "bitwise table", column, i, || Value::known(F::from(*value)))?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Witnesses an operand in its own region, ready to be passed to `apply`
+    pub fn load_private(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "bitwise operand",
+            |mut region| region.assign_advice(|| "operand", self.config.acc_x, 0, || value),
+        )
+    }
+
+    /// Proves `a op b` on `num_chunks * bits` bits and returns the result cell.
+    /// `b` must be `None` exactly for `BitwiseOp::Not`.
+    pub fn apply(
+        &self,
+        mut layouter: impl Layouter<F>,
+        op: BitwiseOp,
+        a: &AssignedCell<F, F>,
+        b: Option<&AssignedCell<F, F>>,
+        num_chunks: usize,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        assert_eq!(op.is_unary(), b.is_none(), "operand count does not match {:?}", op);
+        assert!(num_chunks * self.config.bits <= F::CAPACITY as usize);
+        let bits = self.config.bits;
+        let two_pow_b = F::from(1 << bits);
+
+        let x_chunks = a.value().map(|a| decompose(a, bits, num_chunks));
+        let y_chunks = match b {
+            Some(b) => b.value().map(|b| decompose(b, bits, num_chunks)),
+            None => Value::known(vec![0; num_chunks]),
+        };
+        let z_chunks = x_chunks.clone().zip(y_chunks.clone()).map(|(x, y)| {
+            x.iter().zip(y.iter()).map(|(x, y)| op.apply(*x, *y, bits)).collect::<Vec<_>>()
+        });
+
+        layouter.assign_region(
+            || format!("bitwise {:?}", op),
+            |mut region| {
+                let columns = [
+                    (self.config.x, self.config.acc_x, &x_chunks),
+                    (self.config.y, self.config.acc_y, &y_chunks),
+                    (self.config.z, self.config.acc_z, &z_chunks),
+                ];
+
+                let mut result = None;
+                for (chunk_column, acc_column, chunks) in columns {
+                    // acc_i from the top down, acc_n = 0
+                    let mut acc = Value::known(F::zero());
+                    region.assign_advice(|| "acc end", acc_column, num_chunks, || acc)?;
+                    for i in (0..num_chunks).rev() {
+                        let chunk = chunks.as_ref().map(|chunks| F::from(chunks[i]));
+                        region.assign_advice(|| "chunk", chunk_column, i, || chunk)?;
+                        acc = chunk + acc * Value::known(two_pow_b);
+                        if i > 0 {
+                            region.assign_advice(|| "acc", acc_column, i, || acc)?;
+                        }
+                    }
+
+                    // row 0 is the full value: the operands are copied in, the result is returned
+                    if acc_column == self.config.acc_x {
+                        a.copy_advice(|| "a", &mut region, acc_column, 0)?;
+                    } else if acc_column == self.config.acc_y {
+                        match b {
+                            Some(b) => b.copy_advice(|| "b", &mut region, acc_column, 0)?,
+                            None => region.assign_advice(|| "b", acc_column, 0, || acc)?,
+                        };
+                    } else {
+                        result = Some(region.assign_advice(|| "a op b", acc_column, 0, || acc)?);
+                    }
+                }
+
+                for i in 0..num_chunks {
+                    self.config.q.enable(&mut region, i)?;
+                    region.assign_fixed(|| "op", self.config.op, i, || Value::known(F::from(op.tag())))?;
+                }
+                self.config.q_end.enable(&mut region, num_chunks)?;
+
+                Ok(result.unwrap())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr as Fp,
+    };
+
+    use super::*;
+
+    /// Applies each `(op, a, b)` on 4-bit chunks and exposes the results as public inputs
+    struct BitwiseCircuit {
+        ops: Vec<(BitwiseOp, Value<Fp>, Value<Fp>)>,
+        num_chunks: usize,
+    }
+
+    impl Circuit<Fp> for BitwiseCircuit {
+        type Config = (BitwiseConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            BitwiseCircuit {
+                ops: self.ops.iter().map(|(op, _, _)| (*op, Value::unknown(), Value::unknown())).collect(),
+                num_chunks: self.num_chunks,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let instance = meta.instance_column();
+            meta.enable_equality(instance);
+            (BitwiseChip::configure(meta, 4), instance)
+        }
+
+        fn synthesize(&self, (config, instance): Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = BitwiseChip::<Fp>::construct(config);
+            chip.load_table(layouter.namespace(|| "table"))?;
+            for (i, (op, a, b)) in self.ops.iter().enumerate() {
+                let a = chip.load_private(layouter.namespace(|| "a"), *a)?;
+                let b = if op.is_unary() {
+                    None
+                } else {
+                    Some(chip.load_private(layouter.namespace(|| "b"), *b)?)
+                };
+                let c = chip.apply(layouter.namespace(|| "op"), *op, &a, b.as_ref(), self.num_chunks)?;
+                layouter.constrain_instance(c.cell(), instance, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn verify(ops: &[(BitwiseOp, u64, u64)], num_chunks: usize, results: &[u64]) -> bool {
+        let circuit = BitwiseCircuit {
+            ops: ops
+                .iter()
+                .map(|(op, a, b)| (*op, Value::known(Fp::from(*a)), Value::known(Fp::from(*b))))
+                .collect(),
+            num_chunks,
+        };
+        let instance = results.iter().map(|v| Fp::from(*v)).collect();
+        MockProver::run(10, &circuit, vec![instance]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_bitwise_ops() {
+        let ops = [
+            (BitwiseOp::Xor, 0xa5, 0x3c),
+            (BitwiseOp::And, 0xa5, 0x3c),
+            (BitwiseOp::Or, 0xa5, 0x3c),
+            (BitwiseOp::Not, 0xa5, 0),
+        ];
+        assert!(verify(&ops, 2, &[0x99, 0x24, 0xbd, 0x5a]));
+        assert!(!verify(&ops, 2, &[0x99, 0x24, 0xbd, 0x5b]));
+
+        // 16-bit operands in four chunks
+        assert!(verify(&[(BitwiseOp::Xor, 0xbeef, 0x1234)], 4, &[0xbeef ^ 0x1234]));
+        assert!(verify(&[(BitwiseOp::Not, 0xbeef, 0)], 4, &[0x4110]));
+    }
+
+    #[test]
+    fn test_operand_too_wide() {
+        // 0x1a5 does not fit in two 4-bit chunks, the running sum of `a` cannot reach it
+        assert!(!verify(&[(BitwiseOp::And, 0x1a5, 0xff)], 2, &[0xa5]));
+    }
+}
@@ -0,0 +1,310 @@
+//! Vector lookups compressed with a verifier challenge.
+//!
+//! A tuple `(a_0, .., a_{n-1})` is looked up in a table of `n` advice columns `t` through a
+//! single second-phase column on each side:
+//!
+//! ```text
+//! a_c = a_0 + θ·a_1 + .. + θ^{n-1}·a_{n-1},   t_c = t_0 + θ·t_1 + .. + θ^{n-1}·t_{n-1}
+//! ```
+//!
+//! `θ` is a challenge drawn after the first phase, so `a` and `t` are committed before the
+//! prover learns it. The lookup is `(s, s·a_c)` in `(q_t, q_t·t_c)`, gated like the hardened
+//! `LookupChip` tables so the zero padding of `t_c` is no table entry.
+//!
+//! The lookup argument already compresses its tuples with a challenge of its own, so for a
+//! single lookup this costs two more committed columns than `LookupChip` (see
+//! `cost::compare_vector_lookups`). It pays off once `a_c`/`t_c` are reused, by several
+//! lookups or by other arguments over the same vectors.
+
+use std::ops::{Add, Mul};
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Chip, Layouter, Region, SimpleFloorPlanner, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+use crate::lookup_padding::{LookupChip, LookupConfig, LookupInstructions, LookupStrategy, LookupTable};
+
+#[derive(Clone, Debug)]
+pub struct CompressedLookupConfig {
+    a: Vec<Column<Advice>>,
+    s: Selector,
+    t: Vec<Column<Advice>>,
+    // enabled on every table row the chip loads
+    q_t: Selector,
+    theta: Challenge,
+    // second-phase compressions of `a` and `t`
+    a_c: Column<Advice>,
+    t_c: Column<Advice>,
+}
+
+pub struct CompressedLookupChip<F: FieldExt> {
+    config: CompressedLookupConfig,
+    // the table `load_table` assigned, `None` until then
+    loaded: Option<LookupTable<F>>,
+}
+
+impl<F: FieldExt> Chip<F> for CompressedLookupChip<F> {
+    type Config = CompressedLookupConfig;
+    type Loaded = Option<LookupTable<F>>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &self.loaded
+    }
+}
+
+impl<F: FieldExt> LookupInstructions<F> for CompressedLookupChip<F> {
+    fn witness_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        tuples: &[Vec<Value<F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        if tuples.iter().any(|tuple| tuple.len() != self.config.a.len()) {
+            return Err(Error::Synthesis);
+        }
+        self.assign_inputs(layouter, tuples.len(), |region, i, j, col| {
+            region.assign_advice(|| "a col", col, i, || tuples[i][j])
+        })
+    }
+
+    fn copy_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        inputs: &[Vec<AssignedCell<F, F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        if inputs.iter().any(|tuple| tuple.len() != self.config.a.len()) {
+            return Err(Error::Synthesis);
+        }
+        self.assign_inputs(layouter, inputs.len(), |region, i, j, col| {
+            inputs[i][j].copy_advice(|| "a col", region, col, i)
+        })
+    }
+}
+
+impl<F: FieldExt> CompressedLookupChip<F> {
+    pub fn construct(config: CompressedLookupConfig) -> Self {
+        CompressedLookupChip { config, loaded: None }
+    }
+
+    /// `width` first-phase input and table columns, each side compressed into one
+    /// second-phase column
+    pub fn configure(meta: &mut ConstraintSystem<F>, width: usize) -> CompressedLookupConfig {
+        assert!(width > 0, "a lookup needs at least one column");
+
+        let a = (0..width).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let t = (0..width).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let s = meta.complex_selector();
+        let q_t = meta.complex_selector();
+        let theta = meta.challenge_usable_after(FirstPhase);
+        let a_c = meta.advice_column_in(SecondPhase);
+        let t_c = meta.advice_column_in(SecondPhase);
+
+        for a in a.iter() {
+            meta.enable_equality(*a);
+        }
+
+        meta.create_gate("compress input", |meta| {
+            let s = meta.query_selector(s);
+            let theta = meta.query_challenge(theta);
+            let a = a.iter().map(|a| meta.query_advice(*a, Rotation::cur())).collect::<Vec<_>>();
+            let a_c = meta.query_advice(a_c, Rotation::cur());
+            vec![s * (a_c - compress(&a, &theta))]
+        });
+
+        meta.create_gate("compress table", |meta| {
+            let q_t = meta.query_selector(q_t);
+            let theta = meta.query_challenge(theta);
+            let t = t.iter().map(|t| meta.query_advice(*t, Rotation::cur())).collect::<Vec<_>>();
+            let t_c = meta.query_advice(t_c, Rotation::cur());
+            vec![q_t * (t_c - compress(&t, &theta))]
+        });
+
+        meta.lookup_any("compressed lookup", |meta| {
+            let s = meta.query_selector(s);
+            let q_t = meta.query_selector(q_t);
+            let a_c = meta.query_advice(a_c, Rotation::cur());
+            let t_c = meta.query_advice(t_c, Rotation::cur());
+            vec![(s.clone(), q_t.clone()), (s * a_c, q_t * t_c)]
+        });
+
+        CompressedLookupConfig {
+            a,
+            s,
+            t,
+            q_t,
+            theta,
+            a_c,
+            t_c,
+        }
+    }
+
+    /// Enables `s` on `num_rows` rows of a new region, `assign_cell(region, row, column index, column)`
+    /// fills `a` and `a_c` gets their compression
+    fn assign_inputs(
+        &self,
+        mut layouter: impl Layouter<F>,
+        num_rows: usize,
+        mut assign_cell: impl FnMut(&mut Region<'_, F>, usize, usize, Column<Advice>) -> Result<AssignedCell<F, F>, Error>,
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        // unknown in the first phase, the compressions are only assigned in the second
+        let theta = layouter.get_challenge(self.config.theta);
+        layouter.assign_region(
+            || "compressed inputs",
+            |mut region| {
+                let mut cells = vec![];
+                for i in 0..num_rows {
+                    self.config.s.enable(&mut region, i)?;
+                    let tuple = self
+                        .config
+                        .a
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| assign_cell(&mut region, i, j, *col))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let values = tuple.iter().map(|cell| cell.value().copied()).collect::<Vec<_>>();
+                    region.assign_advice(|| "a_c", self.config.a_c, i, || compress(&values, &theta))?;
+                    cells.push(tuple);
+                }
+                Ok(cells)
+            },
+        )
+    }
+
+    /// Loads `table` into `t` and its compression into `t_c`. A chip loads its table once,
+    /// a second call fails with `Error::Synthesis`.
+    pub fn load_table(&mut self, mut layouter: impl Layouter<F>, table: &LookupTable<F>) -> Result<(), Error> {
+        if self.loaded.is_some() || table.width() != self.config.t.len() {
+            return Err(Error::Synthesis);
+        }
+
+        let theta = layouter.get_challenge(self.config.theta);
+        layouter.assign_region(
+            || "compressed table",
+            |mut region| {
+                for (i, row) in table.rows().enumerate() {
+                    self.config.q_t.enable(&mut region, i)?;
+                    for (col, value) in self.config.t.iter().zip(row.iter()) {
+                        region.assign_advice(|| "t col", *col, i, || Value::known(*value))?;
+                    }
+                    let values = row.iter().map(|v| Value::known(*v)).collect::<Vec<_>>();
+                    region.assign_advice(|| "t_c", self.config.t_c, i, || compress(&values, &theta))?;
+                }
+                Ok(())
+            },
+        )?;
+        self.loaded = Some(table.clone());
+        Ok(())
+    }
+}
+
+/// `v_0 + θ·v_1 + .. + θ^{n-1}·v_{n-1}`, evaluated from the last entry
+pub fn compress<T: Clone + Add<Output = T> + Mul<Output = T>>(values: &[T], theta: &T) -> T {
+    let (last, rest) = values.split_last().expect("a tuple has at least one entry");
+    rest.iter()
+        .rev()
+        .fold(last.clone(), |acc, v| acc * theta.clone() + v.clone())
+}
+
+/// `W`-column lookups of `rows` into `table`, through the hardened advice `LookupChip`
+/// (`COMPRESSED = false`) or the `CompressedLookupChip`
+#[derive(Clone, Debug)]
+pub struct VectorCircuit<F: FieldExt, const W: usize, const COMPRESSED: bool> {
+    rows: Vec<Vec<Value<F>>>,
+    table: LookupTable<F>,
+}
+
+impl<F: FieldExt, const W: usize, const COMPRESSED: bool> VectorCircuit<F, W, COMPRESSED> {
+    pub fn new(rows: Vec<Vec<Value<F>>>, table: LookupTable<F>) -> Self {
+        VectorCircuit { rows, table }
+    }
+
+    fn unknown(&self) -> Self {
+        Self::new(vec![vec![Value::unknown(); W]; self.rows.len()], self.table.clone())
+    }
+}
+
+impl<F: FieldExt, const W: usize> Circuit<F> for VectorCircuit<F, W, false> {
+    type Config = LookupConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.unknown()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        LookupChip::configure(meta, W, LookupStrategy::Advice, true)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let mut chip = LookupChip::<F>::construct(config);
+        chip.witness_lookup(layouter.namespace(|| "inputs"), &self.rows)?;
+        chip.load_table(layouter.namespace(|| "table"), &self.table)
+    }
+}
+
+impl<F: FieldExt, const W: usize> Circuit<F> for VectorCircuit<F, W, true> {
+    type Config = CompressedLookupConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.unknown()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        CompressedLookupChip::configure(meta, W)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let mut chip = CompressedLookupChip::<F>::construct(config);
+        chip.witness_lookup(layouter.namespace(|| "inputs"), &self.rows)?;
+        chip.load_table(layouter.namespace(|| "table"), &self.table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr as Fp};
+
+    use super::*;
+    use crate::lookup_padding::mul_table;
+
+    fn verifies<const COMPRESSED: bool>(rows: &[[u64; 3]]) -> bool
+    where
+        VectorCircuit<Fp, 3, COMPRESSED>: Circuit<Fp>,
+    {
+        let rows = rows
+            .iter()
+            .map(|row| row.iter().map(|v| Value::known(Fp::from(*v))).collect())
+            .collect();
+        let circuit = VectorCircuit::<Fp, 3, COMPRESSED>::new(rows, mul_table());
+        MockProver::run(5, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_compressed_lookup() {
+        assert!(verifies::<true>(&[[2, 3, 6], [4, 4, 16], [1, 1, 1]]));
+        assert!(!verifies::<true>(&[[2, 3, 7]]));
+        // each entry is in the table, but not in this order
+        assert!(!verifies::<true>(&[[3, 6, 2]]));
+        // the zero padding of `t` and `t_c` is not a table row
+        assert!(!verifies::<true>(&[[0, 0, 0]]));
+
+        // the same answers as the native lookup
+        assert!(verifies::<false>(&[[2, 3, 6], [4, 4, 16], [1, 1, 1]]));
+        assert!(!verifies::<false>(&[[3, 6, 2]]));
+    }
+
+    #[test]
+    fn test_compress() {
+        let values = [1u64, 2, 3].map(Fp::from);
+        // 1 + 2·5 + 3·25
+        assert_eq!(compress(&values, &Fp::from(5)), Fp::from(86));
+        assert_eq!(compress(&values[..1], &Fp::from(5)), Fp::one());
+    }
+}
@@ -0,0 +1,239 @@
+//! Cost of the `MyCircuit` lookup configurations for a given input length and table size
+
+use std::fmt;
+
+use halo2_proofs::{
+    circuit::Value,
+    dev::CircuitCost,
+    halo2curves::bn256::{Fr, G1},
+    plonk::{Circuit, ConstraintSystem},
+};
+
+use crate::{
+    compressed::VectorCircuit,
+    lookup_padding::{rows_to_k, LookupStrategy, LookupTable, MyCircuit, StrategyChoice},
+};
+
+#[derive(Clone, Debug)]
+pub struct CostReport {
+    pub strategy: LookupStrategy,
+    pub hardened: bool,
+    pub inputs: usize,
+    pub table_size: usize,
+    pub k: u32,
+    pub advice_columns: usize,
+    /// Includes the `TableColumn`s, which are fixed columns
+    pub fixed_columns: usize,
+    pub table_columns: usize,
+    pub selectors: usize,
+    pub lookups: usize,
+    pub max_degree: usize,
+    /// Bytes of one proof
+    pub proof_size: usize,
+}
+
+impl CostReport {
+    pub const HEADER: &'static str =
+        "strategy     hardened  inputs  table   k  advice  fixed  tables  selectors  lookups  degree  proof";
+
+    /// Measures `circuit` at `2^k` rows
+    pub fn measure<S: StrategyChoice>(k: u32, circuit: &MyCircuit<Fr, S>) -> Self {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let config = MyCircuit::<Fr, S>::configure(&mut cs);
+        let cost = CircuitCost::<G1, MyCircuit<Fr, S>>::measure(k, circuit);
+
+        CostReport {
+            strategy: S::STRATEGY,
+            hardened: S::HARDENED,
+            inputs: circuit.inputs(),
+            table_size: circuit.table().len(),
+            k,
+            advice_columns: cs.num_advice_columns(),
+            fixed_columns: cs.num_fixed_columns(),
+            table_columns: config.num_table_columns(),
+            selectors: cs.num_selectors(),
+            lookups: cs.lookups().len(),
+            max_degree: cs.degree(),
+            proof_size: cost.proof_size(1).into(),
+        }
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<12} {:<9} {:>6} {:>6} {:>3} {:>7} {:>6} {:>7} {:>10} {:>8} {:>7} {:>6}",
+            format!("{:?}", self.strategy),
+            self.hardened,
+            self.inputs,
+            self.table_size,
+            self.k,
+            self.advice_columns,
+            self.fixed_columns,
+            self.table_columns,
+            self.selectors,
+            self.lookups,
+            self.max_degree,
+            self.proof_size,
+        )
+    }
+}
+
+/// One report per `(inputs, table size)` pair, each at its minimum `k`
+pub fn cost_grid<S: StrategyChoice>(inputs: &[usize], table_sizes: &[usize]) -> Vec<CostReport> {
+    let mut reports = vec![];
+    for &table_size in table_sizes {
+        let table = LookupTable::from_fn(table_size, |i| vec![Fr::from(i as u64 + 1)]);
+        for &n in inputs {
+            let circuit = MyCircuit::<Fr, S>::with_table(vec![Value::unknown(); n], table.clone());
+            let k = circuit.min_k();
+            reports.push(CostReport::measure(k, &circuit));
+        }
+    }
+    reports
+}
+
+/// Cost of a `width`-column lookup, native or compressed with a challenge
+#[derive(Clone, Debug)]
+pub struct VectorCost {
+    pub compressed: bool,
+    pub width: usize,
+    pub inputs: usize,
+    pub table_size: usize,
+    pub k: u32,
+    pub advice_columns: usize,
+    pub selectors: usize,
+    pub gates: usize,
+    pub lookups: usize,
+    pub max_degree: usize,
+    /// Bytes of one proof
+    pub proof_size: usize,
+}
+
+impl VectorCost {
+    pub const HEADER: &'static str =
+        "compressed  width  inputs  table   k  advice  selectors  gates  lookups  degree  proof";
+
+    /// Measures `inputs` lookups into `table` at `2^k` rows
+    fn measure<const W: usize, const COMPRESSED: bool>(
+        k: u32,
+        inputs: usize,
+        table: &LookupTable<Fr>,
+    ) -> Self
+    where
+        VectorCircuit<Fr, W, COMPRESSED>: Circuit<Fr>,
+    {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        VectorCircuit::<Fr, W, COMPRESSED>::configure(&mut cs);
+        let circuit = VectorCircuit::<Fr, W, COMPRESSED>::new(vec![vec![Value::unknown(); W]; inputs], table.clone());
+        let cost = CircuitCost::<G1, VectorCircuit<Fr, W, COMPRESSED>>::measure(k, &circuit);
+
+        VectorCost {
+            compressed: COMPRESSED,
+            width: W,
+            inputs,
+            table_size: table.len(),
+            k,
+            advice_columns: cs.num_advice_columns(),
+            selectors: cs.num_selectors(),
+            gates: cs.gates().len(),
+            lookups: cs.lookups().len(),
+            max_degree: cs.degree(),
+            proof_size: cost.proof_size(1).into(),
+        }
+    }
+}
+
+impl fmt::Display for VectorCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<11} {:>5} {:>7} {:>6} {:>3} {:>7} {:>10} {:>6} {:>8} {:>7} {:>6}",
+            self.compressed,
+            self.width,
+            self.inputs,
+            self.table_size,
+            self.k,
+            self.advice_columns,
+            self.selectors,
+            self.gates,
+            self.lookups,
+            self.max_degree,
+            self.proof_size,
+        )
+    }
+}
+
+/// The hardened advice `LookupChip` and the `CompressedLookupChip` on the same `W`-column
+/// table of `table_size` rows with `inputs` lookups, both at the `k` the larger one needs
+pub fn compare_vector_lookups<const W: usize>(inputs: usize, table_size: usize) -> [VectorCost; 2]
+where
+    VectorCircuit<Fr, W, false>: Circuit<Fr>,
+    VectorCircuit<Fr, W, true>: Circuit<Fr>,
+{
+    let table = LookupTable::from_fn(table_size, |i| {
+        (0..W).map(|j| Fr::from((i * W + j) as u64 + 1)).collect()
+    });
+    let mut native = ConstraintSystem::<Fr>::default();
+    let config = VectorCircuit::<Fr, W, false>::configure(&mut native);
+    // the native table also loads its default rows
+    let rows = inputs.max(config.table_rows(&table));
+    let mut compressed = ConstraintSystem::<Fr>::default();
+    VectorCircuit::<Fr, W, true>::configure(&mut compressed);
+    let k = rows_to_k(&native, rows).max(rows_to_k(&compressed, rows));
+
+    [
+        VectorCost::measure::<W, false>(k, inputs, &table),
+        VectorCost::measure::<W, true>(k, inputs, &table),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lookup_padding::{AdviceLookup, FixedLookup, TableColumnLookup};
+
+    #[test]
+    fn test_cost_grid() {
+        let reports = cost_grid::<AdviceLookup>(&[4, 64], &[9, 4096]);
+        assert_eq!(reports.len(), 4);
+        // 4 inputs and 10 table rows fit in 2^4 rows next to the blinding rows
+        assert_eq!(reports[0].k, 4);
+        assert!(reports[1].k > reports[0].k);
+        // 4097 table rows need 2^13 rows
+        assert_eq!(reports[2].k, 13);
+        assert_eq!(reports[3].k, 13);
+        for report in reports.iter() {
+            assert_eq!((report.advice_columns, report.table_columns, report.lookups), (2, 0, 1));
+        }
+    }
+
+    #[test]
+    fn test_cost_across_strategies() {
+        let table = cost_grid::<TableColumnLookup>(&[4], &[9]).remove(0);
+        let advice = cost_grid::<AdviceLookup>(&[4], &[9]).remove(0);
+        let fixed = cost_grid::<FixedLookup>(&[4], &[9]).remove(0);
+        let hardened = cost_grid::<AdviceLookup<true>>(&[4], &[9]).remove(0);
+
+        assert_eq!(table.table_columns, 1);
+        // the advice table is committed in the proof, fixed and TableColumns are in the vk
+        assert!(advice.proof_size > table.proof_size);
+        assert!(advice.proof_size > fixed.proof_size);
+        // the hardened tag adds a lookup column, and with it a selector and a degree
+        assert!(hardened.max_degree > advice.max_degree);
+        assert_eq!(hardened.selectors, advice.selectors + 1);
+    }
+
+    #[test]
+    fn test_compressed_vs_native() {
+        let [native, compressed] = compare_vector_lookups::<4>(16, 64);
+        assert_eq!(native.k, compressed.k);
+        assert_eq!((native.lookups, compressed.lookups), (1, 1));
+        // `a_c` and `t_c` are two more committed columns, with the gates defining them
+        assert_eq!(compressed.advice_columns, native.advice_columns + 2);
+        assert_eq!(compressed.gates, native.gates + 2);
+        assert!(compressed.max_degree <= native.max_degree);
+        assert!(compressed.proof_size > native.proof_size);
+    }
+}
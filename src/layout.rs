@@ -0,0 +1,70 @@
+//! Renders `MyCircuit` with the `dev-graph` tooling: a PNG of the region layout and the
+//! DOT graph of its namespaces.
+//!
+//! The PNG shows where the "a,b" region, the table region ("t2"/"t3"/"t4") and the `t1` table land.
+//! Columns are drawn for all `2^k` rows, so the rows nothing was assigned to, which the prover
+//! pads, are the blank part of each column below its region.
+
+use std::{error::Error, fs, path::Path};
+
+use halo2_proofs::{
+    dev::{circuit_dot_graph, CircuitLayout},
+    halo2curves::bn256::Fr,
+};
+use plotters::prelude::*;
+
+use crate::lookup_padding::{MyCircuit, StrategyChoice};
+
+/// Writes the layout of `circuit` at `2^k` rows to `png`
+pub fn render_layout<S: StrategyChoice>(
+    k: u32,
+    circuit: &MyCircuit<Fr, S>,
+    png: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let root = BitMapBackend::new(png, (1024, 768)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let root = root.titled("MyCircuit layout", ("sans-serif", 40))?;
+
+    CircuitLayout::default()
+        .show_labels(true)
+        .mark_equality_cells(true)
+        .show_equality_constraints(true)
+        .render(k, circuit, &root)?;
+    root.present()?;
+    Ok(())
+}
+
+/// Writes the DOT graph of `circuit` to `dot` and returns it
+pub fn render_dot_graph<S: StrategyChoice>(
+    circuit: &MyCircuit<Fr, S>,
+    dot: &Path,
+) -> Result<String, Box<dyn Error>> {
+    let graph = circuit_dot_graph(circuit);
+    fs::write(dot, &graph)?;
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::circuit::Value;
+
+    use super::*;
+    use crate::lookup_padding::{AdviceLookup, TableColumnLookup};
+
+    #[test]
+    fn test_render_layout() {
+        let a = [0, 1, 2, 3].map(|v| Value::known(Fr::from(v))).to_vec();
+        let dir = std::env::temp_dir().join(format!("lookup_test_render_layout_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let circuit = MyCircuit::<Fr, AdviceLookup>::new(a.clone());
+        render_layout(5, &circuit, &dir.join("advice.png")).unwrap();
+        assert!(render_dot_graph(&circuit, &dir.join("advice.dot")).unwrap().starts_with("digraph"));
+
+        let circuit = MyCircuit::<Fr, TableColumnLookup>::new(a);
+        render_layout(5, &circuit, &dir.join("table.png")).unwrap();
+        assert!(fs::metadata(dir.join("table.png")).unwrap().len() > 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,34 @@
+//! Lookup arguments in halo2 and the padding hole of tables queried from advice and fixed
+//! columns.
+//!
+//! - [`lookup_padding`]: `LookupChip` over `TableColumn`, advice or fixed tables, optionally
+//!   hardened against the zero padding, and the `MyCircuit` example circuit
+//! - [`range_check`], [`bitwise`], [`tagged_table`]: chips built on top of lookups
+//! - [`compressed`]: vector lookups compressed with a second-phase challenge
+//! - [`shuffle`]: multiset equality with a grand product, where a lookup only checks membership
+//! - [`memory`]: read/write memory consistency of an execution trace
+//! - [`audit`]: reports the table tuples only the padding of unassigned rows adds
+//! - [`prover`], [`cost`]: KZG proving pipeline and cost reports of the lookup configurations
+//! - [`artifact`]: params, verifying keys and proofs on disk, with a header naming their circuit
+//! - [`scenario`]: `MyCircuit` witnesses, tables, strategies and `k` read from JSON
+
+pub mod artifact;
+pub mod audit;
+pub mod bitwise;
+pub mod compressed;
+pub mod cost;
+#[cfg(feature = "dev-graph")]
+pub mod layout;
+pub mod lookup_padding;
+pub mod memory;
+pub mod prover;
+pub mod range_check;
+pub mod scenario;
+pub mod shuffle;
+pub mod tagged_table;
+
+pub use lookup_padding::{
+    AdviceLookup, FixedLookup, InstanceCopyLookup, InstanceLookup, LookupChip, LookupConfig,
+    LookupInstructions, LookupRotations, LookupStrategy, LookupTable, MyCircuit, MyCircuitBuilder,
+    RegionOverflow, StrategyChoice, TableColumnLookup,
+};
@@ -1,159 +1,1623 @@
-/// A circuit to demonstrate we can do lookup on different rows in different columns
+//! A circuit to demonstrate we can do lookup on different rows in different columns
 // mod bad_lookup;
 
-use std::marker::PhantomData;
+use std::{fmt, marker::PhantomData};
 
 use halo2_proofs::{
-    circuit::{Layouter, SimpleFloorPlanner, Value},
+    circuit::{AssignedCell, Chip, Layouter, Region, SimpleFloorPlanner, Value},
     plonk::*,
     poly::Rotation,
     arithmetic::FieldExt,
 };
 
-#[derive(Clone)]
-struct LookupConfig {
-    a: Column<Advice>,
+/// Which kind of table `LookupChip::configure` looks the inputs up in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LookupStrategy {
+    /// `meta.lookup` into `TableColumn`s, unassigned rows repeat the first table row. The
+    /// table is part of the verifying key.
+    TableColumn,
+    /// `meta.lookup_any` into advice columns, unassigned rows are zero. The prover assigns the
+    /// table, so the verifying key does not fix its values: a prover can load any table with
+    /// the same rows, hardened or not.
+    Advice,
+    /// `meta.lookup_any` into fixed columns, unassigned rows are zero. The table is part of
+    /// the verifying key.
+    Fixed,
+    /// `meta.lookup_any` into instance columns, rows past the public inputs are zero. Plain
+    /// only: the lookup reads the instance columns at absolute rows, but the chip could only
+    /// tag table rows at offsets of a region it does not place. Use `InstanceCopy` for a
+    /// hardened public table.
+    Instance,
+    /// `meta.lookup_any` into advice columns copied from instance columns with equality
+    /// constraints, unassigned rows are zero
+    InstanceCopy,
+}
+
+impl std::str::FromStr for LookupStrategy {
+    type Err = String;
+
+    /// The names the command line and scenario files use
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "table" => Ok(LookupStrategy::TableColumn),
+            "advice" => Ok(LookupStrategy::Advice),
+            "fixed" => Ok(LookupStrategy::Fixed),
+            "instance" => Ok(LookupStrategy::Instance),
+            "instance-copy" => Ok(LookupStrategy::InstanceCopy),
+            other => Err(format!("unknown strategy {}", other)),
+        }
+    }
+}
+
+/// Where each entry of a looked up tuple is queried, as `(column, rotation)` pairs on the input
+/// columns `a` and on the table columns. Input rotations are relative to the row the lookup is
+/// enabled on, table rotations to the row the table tuple is read from, so `a[i]` can be
+/// checked against `t[i + 1]` or a tuple built from `a[i - 1], a[i]`. Rotated table queries
+/// need `hardened` to be sound, see `LookupConfig::default_rows`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupRotations {
+    input: Vec<(usize, Rotation)>,
+    table: Vec<(usize, Rotation)>,
+}
+
+impl LookupRotations {
+    /// `(a[0], .., a[n-1])` in `(t[0], .., t[n-1])`, all on the current row
+    pub fn current(width: usize) -> Self {
+        let entries = (0..width).map(|j| (j, Rotation::cur())).collect::<Vec<_>>();
+        Self::new(entries.clone(), entries)
+    }
+
+    pub fn new(input: Vec<(usize, Rotation)>, table: Vec<(usize, Rotation)>) -> Self {
+        assert!(!input.is_empty(), "a lookup needs at least one column");
+        assert_eq!(input.len(), table.len(), "input and table tuples must have the same width");
+        LookupRotations { input, table }
+    }
+
+    /// Entries of the looked up tuple
+    pub fn width(&self) -> usize {
+        self.input.len()
+    }
+
+    fn num_columns(entries: &[(usize, Rotation)]) -> usize {
+        entries.iter().map(|(column, _)| column + 1).max().unwrap_or(0)
+    }
+
+    /// Lowest and highest rotation of `entries`, counting the current row
+    fn span(entries: &[(usize, Rotation)]) -> (i32, i32) {
+        entries
+            .iter()
+            .fold((0, 0), |(lo, hi), (_, rotation)| (lo.min(rotation.0), hi.max(rotation.0)))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LookupConfig {
+    // the tuple `(a[0], .., a[n-1])` is looked up in `(t[0], .., t[n-1])`, each entry queried
+    // at its rotation
+    a: Vec<Column<Advice>>,
     s: Selector,
-    t1: TableColumn,
-    t2: Column<Advice>
+    rotations: LookupRotations,
+    strategy: LookupStrategy,
+    // only the table columns of `strategy` are allocated, the others stay empty
+    t1: Vec<TableColumn>,
+    t2: Vec<Column<Advice>>,
+    t3: Vec<Column<Fixed>>,
+    // the public table of `Instance` lookups, and the source of the `t2` copy of `InstanceCopy`
+    t4: Vec<Column<Instance>>,
+    // hardened `t1` lookups: 0 on the default row, 1 on every loaded table row
+    t1_tag: Option<TableColumn>,
+    // hardened `t2`/`t3`/`t4` lookups: enabled on every table row the chip assigns
+    q_t: Option<Selector>,
+    // when set, only table rows the chip loaded count as table entries (see `configure`)
+    hardened: bool,
 }
 
-struct LookupChip<F: FieldExt> {
+impl LookupConfig {
+    /// Number of entries in each looked up tuple
+    pub fn width(&self) -> usize {
+        self.rotations.width()
+    }
+
+    /// Number of input columns, the width of each row passed to `LookupInstructions`
+    pub fn num_input_columns(&self) -> usize {
+        self.a.len()
+    }
+
+    /// Number of table columns, the width of the `LookupTable` the chip loads
+    fn table_width(&self) -> usize {
+        LookupRotations::num_columns(&self.rotations.table)
+    }
+
+    /// The rows `load_table` puts in the table columns: rows of ones for the default tuple
+    /// (a single row without rotations) followed by `table`
+    fn padded_rows<F: FieldExt>(&self, table: &LookupTable<F>) -> Vec<Vec<F>> {
+        let default_rows = self.default_rows();
+        std::iter::repeat(vec![F::one(); self.table_width()])
+            .take(default_rows)
+            .chain(table.rows().cloned())
+            .collect()
+    }
+
+    /// Enough rows of ones for every rotation of the default tuple to land on them.
+    ///
+    /// With table rotations, a plain table also holds the tuples read across the last default
+    /// row and the first table rows: `(t[i], t[i + 1])` reads `(1, table[0])`, which is no
+    /// entry of `table`, the same hole the zero padding leaves. Only `hardened` tables, whose
+    /// tag is off on the default rows, keep them out.
+    fn default_rows(&self) -> usize {
+        let (lo, hi) = LookupRotations::span(&self.rotations.table);
+        (hi - lo + 1) as usize
+    }
+
+    /// The public inputs of the instance columns holding `table`, one vector per column in the
+    /// order they were allocated, for the `Instance` and `InstanceCopy` strategies. The
+    /// default rows come first, like in every other table.
+    pub fn table_instance<F: FieldExt>(&self, table: &LookupTable<F>) -> Vec<Vec<F>> {
+        let rows = self.padded_rows(table);
+        (0..self.t4.len())
+            .map(|j| rows.iter().map(|row| row[j]).collect())
+            .collect()
+    }
+
+    /// `TableColumn`s allocated for the `TableColumn` strategy, including the hardened tag
+    pub fn num_table_columns(&self) -> usize {
+        self.t1.len() + self.t1_tag.iter().count()
+    }
+
+    /// Rows `load_table` fills with `table`, the default rows included
+    pub fn table_rows<F: FieldExt>(&self, table: &LookupTable<F>) -> usize {
+        self.default_rows() + table.len()
+    }
+
+    /// Fails with `NotEnoughRowsAvailable` when the `table_rows` of `table` do not fit in the
+    /// usable rows of `k`, i.e. `2^k` minus the blinding rows of `cs`
+    pub fn check_fits<F: FieldExt>(
+        &self,
+        table: &LookupTable<F>,
+        k: u32,
+        cs: &ConstraintSystem<F>,
+    ) -> Result<(), Error> {
+        if self.table_rows(table) > usable_rows(k, cs) {
+            return Err(Error::NotEnoughRowsAvailable { current_k: k });
+        }
+        Ok(())
+    }
+
+    /// Name of the region or table `load_table` assigns
+    fn table_region(&self) -> &'static str {
+        match self.strategy {
+            LookupStrategy::TableColumn => "t1",
+            LookupStrategy::Advice | LookupStrategy::InstanceCopy => "t2",
+            LookupStrategy::Fixed => "t3",
+            LookupStrategy::Instance => "t4",
+        }
+    }
+}
+
+/// Rows of `2^k` a circuit can assign, the others hold the blinding factors of `cs`
+pub fn usable_rows<F: FieldExt>(k: u32, cs: &ConstraintSystem<F>) -> usize {
+    (1usize << k).saturating_sub(cs.blinding_factors() + 1)
+}
+
+/// Smallest `k` with `rows` usable rows next to the blinding rows of `cs`, and at least
+/// the minimum rows its lookups need
+pub fn rows_to_k<F: FieldExt>(cs: &ConstraintSystem<F>, rows: usize) -> u32 {
+    let rows = (rows + cs.blinding_factors() + 1).max(cs.minimum_rows());
+    rows.next_power_of_two().trailing_zeros()
+}
+
+/// A region or table that does not fit in the usable rows of `2^k`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionOverflow {
+    pub region: &'static str,
+    pub rows: usize,
+    pub k: u32,
+    pub usable_rows: usize,
+}
+
+impl fmt::Display for RegionOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "region `{}` needs {} rows, k = {} only has {} usable rows",
+            self.region, self.rows, self.k, self.usable_rows
+        )
+    }
+}
+
+impl std::error::Error for RegionOverflow {}
+
+impl From<RegionOverflow> for Error {
+    fn from(overflow: RegionOverflow) -> Self {
+        Error::NotEnoughRowsAvailable { current_k: overflow.k }
+    }
+}
+
+/// Lookups into the table a chip loaded once with its `load_table` step.
+/// Any number of callers in a circuit can share that table.
+///
+/// Each entry of `tuples`/`inputs` is one row of the input columns, which is the looked up
+/// tuple when every entry is queried on the current row. With other rotations, the rows
+/// whose tuple would read outside the region are not looked up.
+pub trait LookupInstructions<F: FieldExt>: Chip<F> {
+    /// Witnesses each tuple of `tuples` and looks it up, returns the assigned cells
+    fn witness_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        tuples: &[Vec<Value<F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error>;
+
+    /// Copies each tuple of cells assigned by other chips and looks it up,
+    /// returns the copies so callers can keep constraining them
+    fn copy_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        inputs: &[Vec<AssignedCell<F, F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error>;
+}
+
+pub struct LookupChip<F: FieldExt> {
     config: LookupConfig,
-    _marker: PhantomData<F>,
+    // the table `load_table` assigned, `None` until then
+    loaded: Option<LookupTable<F>>,
 }
 
-impl<F: FieldExt> LookupChip<F> {
-    fn construct(config: LookupConfig) -> Self {
-        LookupChip {
-            config,
-            _marker: PhantomData,
+impl<F: FieldExt> Chip<F> for LookupChip<F> {
+    type Config = LookupConfig;
+    type Loaded = Option<LookupTable<F>>;
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &self.loaded
+    }
+}
+
+impl<F: FieldExt> LookupInstructions<F> for LookupChip<F> {
+    fn witness_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        tuples: &[Vec<Value<F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        if tuples.iter().any(|tuple| tuple.len() != self.config.num_input_columns()) {
+            return Err(Error::Synthesis);
+        }
+        self.assign_inputs(layouter, tuples.len(), |region, i, j, col| {
+            region.assign_advice(|| "a col", col, i, || tuples[i][j])
+        })
+    }
+
+    fn copy_lookup(
+        &self,
+        layouter: impl Layouter<F>,
+        inputs: &[Vec<AssignedCell<F, F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        if inputs.iter().any(|tuple| tuple.len() != self.config.num_input_columns()) {
+            return Err(Error::Synthesis);
         }
+        self.assign_inputs(layouter, inputs.len(), |region, i, j, col| {
+            inputs[i][j].copy_advice(|| "a col", region, col, i)
+        })
+    }
+}
+
+impl<F: FieldExt> LookupChip<F> {
+    pub fn construct(config: LookupConfig) -> Self {
+        LookupChip { config, loaded: None }
+    }
+
+    /// Allocates `width` input columns and the table columns of `strategy`. A `hardened`
+    /// table only matches the rows `load_table` assigned, `Instance` tables cannot be hardened.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        width: usize,
+        strategy: LookupStrategy,
+        hardened: bool,
+    ) -> LookupConfig {
+        Self::configure_with_rotations(meta, LookupRotations::current(width), strategy, hardened)
     }
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> LookupConfig {
-        let a = meta.advice_column();
+    /// `configure` with the tuple entries queried at `rotations`. `TableColumn` tables have
+    /// no rotations, only their inputs can be rotated.
+    pub fn configure_with_rotations(
+        meta: &mut ConstraintSystem<F>,
+        rotations: LookupRotations,
+        strategy: LookupStrategy,
+        hardened: bool,
+    ) -> LookupConfig {
+        let width = rotations.width();
+        let table_width = LookupRotations::num_columns(&rotations.table);
+        if strategy == LookupStrategy::TableColumn {
+            assert_eq!(
+                rotations.table,
+                LookupRotations::current(width).table,
+                "TableColumn lookups read the table on the current row"
+            );
+        }
+        assert!(
+            !(strategy == LookupStrategy::Instance && hardened),
+            "Instance tables cannot be hardened, use InstanceCopy"
+        );
+
+        let a = (0..LookupRotations::num_columns(&rotations.input))
+            .map(|_| meta.advice_column())
+            .collect::<Vec<_>>();
         let s = meta.complex_selector();
-        let t1 = meta.lookup_table_column();
-        let t2 = meta.advice_column();
 
-        meta.enable_equality(a);
+        for a in a.iter() {
+            meta.enable_equality(*a);
+        }
 
-        let one = Expression::Constant(F::one());
+        let mut config = LookupConfig {
+            a,
+            s,
+            rotations,
+            strategy,
+            t1: vec![],
+            t2: vec![],
+            t3: vec![],
+            t4: vec![],
+            t1_tag: None,
+            q_t: None,
+            hardened,
+        };
 
-        // meta.lookup("lookup", |meta| {
-        //     let cur_a = meta.query_advice(a, Rotation::cur());
-        //     let s = meta.query_selector(s);
-        //     // we'll assgin (0,0) in t1,t2 table
-        //     // so the default condition for other rows without need to lookup will also satisfy this constriant
-        //     vec![(s.clone() * cur_a + ( one.clone() - s) * one.clone(), t1)]
-        // });
+        match strategy {
+            LookupStrategy::TableColumn => {
+                config.t1 = (0..table_width).map(|_| meta.lookup_table_column()).collect();
+                if hardened {
+                    config.t1_tag = Some(meta.lookup_table_column());
+                }
+                let (t1, t1_tag) = (config.t1.clone(), config.t1_tag);
+                meta.lookup("lookup", |meta| {
+                    let inputs = Self::input_expressions(meta, &config, hardened);
+                    // the tag column lines up with the leading `s` of the hardened input tuple
+                    inputs.into_iter().zip(t1_tag.into_iter().chain(t1)).collect()
+                });
+            }
+            LookupStrategy::Advice | LookupStrategy::InstanceCopy => {
+                config.t2 = (0..table_width).map(|_| meta.advice_column()).collect();
+                if strategy == LookupStrategy::InstanceCopy {
+                    config.t4 = (0..table_width).map(|_| meta.instance_column()).collect();
+                    for (t2, t4) in config.t2.iter().zip(config.t4.iter()) {
+                        meta.enable_equality(*t2);
+                        meta.enable_equality(*t4);
+                    }
+                }
+                config.q_t = hardened.then(|| meta.complex_selector());
+                let (t2, q_t) = (config.t2.clone(), config.q_t);
+                meta.lookup_any("lookup_any", |meta| {
+                    let inputs = Self::input_expressions(meta, &config, hardened);
+                    let table = config
+                        .rotations
+                        .table
+                        .iter()
+                        .map(|(column, rotation)| meta.query_advice(t2[*column], *rotation))
+                        .collect();
+                    inputs.into_iter().zip(Self::gate_table(meta, q_t, table)).collect()
+                });
+            }
+            LookupStrategy::Fixed => {
+                config.t3 = (0..table_width).map(|_| meta.fixed_column()).collect();
+                config.q_t = hardened.then(|| meta.complex_selector());
+                let (t3, q_t) = (config.t3.clone(), config.q_t);
+                meta.lookup_any("lookup_fixed", |meta| {
+                    let inputs = Self::input_expressions(meta, &config, hardened);
+                    let table = config
+                        .rotations
+                        .table
+                        .iter()
+                        .map(|(column, rotation)| meta.query_fixed(t3[*column], *rotation))
+                        .collect();
+                    inputs.into_iter().zip(Self::gate_table(meta, q_t, table)).collect()
+                });
+            }
+            LookupStrategy::Instance => {
+                config.t4 = (0..table_width).map(|_| meta.instance_column()).collect();
+                let t4 = config.t4.clone();
+                meta.lookup_any("lookup_instance", |meta| {
+                    let inputs = Self::input_expressions(meta, &config, false);
+                    let table = config
+                        .rotations
+                        .table
+                        .iter()
+                        .map(|(column, rotation)| meta.query_instance(t4[*column], *rotation))
+                        .collect::<Vec<_>>();
+                    inputs.into_iter().zip(table).collect()
+                });
+            }
+        }
+
+        config
+    }
 
-        meta.lookup_any("lookup_any", |meta| {
-            let cur_a = meta.query_advice(a, Rotation::cur());
-            let table = meta.query_advice(t2, Rotation::cur());
-            let s = meta.query_selector(s);
-            // we'll assgin (0,0) in t1,t2 table
-            // so the default condition for other rows without need to lookup will also satisfy this constriant
-            vec![(s.clone() * cur_a + ( one.clone() - s) * one.clone(), table)]
-        });
+    /// The looked up tuple of the current row, each entry of `a` at its rotation.
+    ///
+    /// Plain: `s * a + (1 - s) * 1` per column. We'll assign (1,..,1) in the table,
+    /// so the default condition for other rows without need to lookup will also satisfy this constraint.
+    ///
+    /// Hardened: `(s, s * a[0], ..)`. `s` is the matching tag, so a disabled row looks up
+    /// (0, 0,..) and an enabled row can only hit a table row tagged 1 by the chip.
+    fn input_expressions(
+        meta: &mut VirtualCells<'_, F>,
+        config: &LookupConfig,
+        hardened: bool,
+    ) -> Vec<Expression<F>> {
+        let one = Expression::Constant(F::one());
+        let s = meta.query_selector(config.s);
+        let a = config
+            .rotations
+            .input
+            .iter()
+            .map(|(column, rotation)| meta.query_advice(config.a[*column], *rotation))
+            .collect::<Vec<_>>();
+        if hardened {
+            std::iter::once(s.clone())
+                .chain(a.into_iter().map(|cur_a| s.clone() * cur_a))
+                .collect()
+        } else {
+            a.into_iter()
+                .map(|cur_a| s.clone() * cur_a + (one.clone() - s.clone()) * one.clone())
+                .collect()
+        }
+    }
 
-        LookupConfig { a,  s, t1, t2}
+    /// Hardened: `(q_t, q_t * t[0], ..)`, every row the chip did not tag (including the
+    /// zero padding) collapses to (0, 0,..), which only disabled inputs look up
+    fn gate_table(
+        meta: &mut VirtualCells<'_, F>,
+        q_t: Option<Selector>,
+        table: Vec<Expression<F>>,
+    ) -> Vec<Expression<F>> {
+        match q_t {
+            Some(q_t) => {
+                let q_t = meta.query_selector(q_t);
+                std::iter::once(q_t.clone())
+                    .chain(table.into_iter().map(|t| q_t.clone() * t))
+                    .collect()
+            }
+            None => table,
+        }
     }
 
-    fn assign(
+    /// Assigns `num_rows` rows of a new region, `assign_cell(region, row, column index, column)`
+    /// fills `a`. `s` is enabled on every row whose rotated tuple stays inside the region.
+    fn assign_inputs(
         &self,
         mut layouter: impl Layouter<F>,
-        a_arr: &Vec<Value<F>>,
-    ) -> Result<(), Error> {
+        num_rows: usize,
+        mut assign_cell: impl FnMut(&mut Region<'_, F>, usize, usize, Column<Advice>) -> Result<AssignedCell<F, F>, Error>,
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
         layouter.assign_region(
             || "a,b",
             |mut region| {
-                for i in 0..a_arr.len() {
-                    self.config.s.enable(&mut region, i)?;
-                    region.assign_advice(|| "a col", self.config.a, i, || a_arr[i])?;
+                let (lo, hi) = LookupRotations::span(&self.config.rotations.input);
+                let mut cells = vec![];
+                for i in 0..num_rows {
+                    if i as i64 + lo as i64 >= 0 && i as i64 + (hi as i64) < num_rows as i64 {
+                        self.config.s.enable(&mut region, i)?;
+                    }
+                    let tuple = self
+                        .config
+                        .a
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| assign_cell(&mut region, i, j, *col))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    cells.push(tuple);
                 }
-                Ok(())
+                Ok(cells)
             },
-        )?;
+        )
+    }
 
-        layouter.assign_region(
-            || "t2",
-            |mut region| {
-                region.assign_advice(|| "t2 col", self.config.t2, 0, || Value::known(F::from(1 as u64)))?;
-                for i in 1..10 {
-                    region.assign_advice(|| "t2 col", self.config.t2, i, || Value::known(F::from(i as u64)))?;
-                }
-                Ok(())
-            },
-        )?;
-
-        layouter.assign_table(
-            || "t1",
-            |mut table| {
-                table.assign_cell(
-                    || "t1",
-                    self.config.t1,
-                    0,
-                    || Value::known(F::from(1 as u64)),
-                )?;
-                for i in 1..10 {
-                    table.assign_cell(
-                        || "t1",
-                        self.config.t1,
-                        i,
-                        || Value::known(F::from(i as u64)),
-                    )?;
-                }
+    /// Loads `table` into the table columns of the configured strategy. A chip loads its table
+    /// once, a second call fails with `Error::Synthesis`.
+    pub fn load_table(&mut self, layouter: impl Layouter<F>, table: &LookupTable<F>) -> Result<(), Error> {
+        if self.loaded.is_some() {
+            return Err(Error::Synthesis);
+        }
+        self.assign_table(layouter, table)?;
+        self.loaded = Some(table.clone());
+        Ok(())
+    }
 
-                Ok(())
-            },
-        )?;
+    fn assign_table(&self, mut layouter: impl Layouter<F>, table: &LookupTable<F>) -> Result<(), Error> {
+        let width = self.config.table_width();
+        if table.width() != width {
+            return Err(Error::Synthesis);
+        }
 
-        Ok(())
+        // the table starts with rows of ones for the default tuple (1,..,1) used by disabled
+        // rows, the table rows follow
+        let (lo, hi) = LookupRotations::span(&self.config.rotations.table);
+        let default_rows = self.config.default_rows();
+        let rows = self.config.padded_rows(table);
+        // the rows whose rotated tuple only reads loaded table rows, the ones `q_t` tags
+        let tagged = |i: usize| {
+            i as i64 + lo as i64 >= default_rows as i64
+                && i as i64 + (hi as i64) < (default_rows + table.len()) as i64
+        };
+
+        match self.config.strategy {
+            LookupStrategy::TableColumn => layouter.assign_table(
+                || "t1",
+                |mut t| {
+                    for (i, row) in rows.iter().enumerate() {
+                        // hardened: the default row becomes (0, 0,..), the padding repeats it
+                        let (tag, row) = if i == 0 && self.config.hardened {
+                            (F::zero(), vec![F::zero(); width])
+                        } else {
+                            (F::one(), row.clone())
+                        };
+                        if let Some(t1_tag) = self.config.t1_tag {
+                            t.assign_cell(|| "t1 tag", t1_tag, i, || Value::known(tag))?;
+                        }
+                        for (col, value) in self.config.t1.iter().zip(row.iter()) {
+                            t.assign_cell(|| "t1", *col, i, || Value::known(*value))?;
+                        }
+                    }
+
+                    Ok(())
+                },
+            ),
+            LookupStrategy::Advice => layouter.assign_region(
+                || "t2",
+                |mut region| {
+                    for (i, row) in rows.iter().enumerate() {
+                        for (col, value) in self.config.t2.iter().zip(row.iter()) {
+                            region.assign_advice(|| "t2 col", *col, i, || Value::known(*value))?;
+                        }
+                        // the default tuple only has to be a table entry for the plain lookup, and
+                        // neither it nor a tuple reading past the table is one in the hardened table
+                        if let Some(q_t) = self.config.q_t {
+                            if tagged(i) {
+                                q_t.enable(&mut region, i)?;
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            ),
+            LookupStrategy::Fixed => layouter.assign_region(
+                || "t3",
+                |mut region| {
+                    for (i, row) in rows.iter().enumerate() {
+                        for (col, value) in self.config.t3.iter().zip(row.iter()) {
+                            region.assign_fixed(|| "t3 col", *col, i, || Value::known(*value))?;
+                        }
+                        if let Some(q_t) = self.config.q_t {
+                            if tagged(i) {
+                                q_t.enable(&mut region, i)?;
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            ),
+            // the values are the public inputs, there is nothing to assign or tag
+            LookupStrategy::Instance => Ok(()),
+            LookupStrategy::InstanceCopy => layouter.assign_region(
+                || "t2",
+                |mut region| {
+                    for (i, row) in rows.iter().enumerate() {
+                        let columns = self.config.t2.iter().zip(self.config.t4.iter());
+                        for ((t2, t4), value) in columns.zip(row.iter()) {
+                            let cell = region.assign_advice_from_instance(|| "t2 col", *t4, i, *t2, i)?;
+                            // the public inputs must hold `table`, e.g. not be shorter than it
+                            cell.value().error_if_known_and(|v| *v != value)?;
+                        }
+                        if let Some(q_t) = self.config.q_t {
+                            if tagged(i) {
+                                q_t.enable(&mut region, i)?;
+                            }
+                        }
+                    }
+                    Ok(())
+                },
+            ),
+        }
+    }
+}
+
+/// The tuples loaded into the table columns `t1`..`t4`, one `Vec<F>` of `width` values per row
+#[derive(Clone, Debug)]
+pub struct LookupTable<F: FieldExt> {
+    rows: Vec<Vec<F>>,
+}
+
+impl<F: FieldExt> LookupTable<F> {
+    /// A single column table holding `values`
+    pub fn from_values(values: impl IntoIterator<Item = F>) -> Self {
+        Self::from_rows(values.into_iter().map(|v| vec![v]).collect())
+    }
+
+    pub fn from_rows(rows: Vec<Vec<F>>) -> Self {
+        assert!(!rows.is_empty(), "a lookup table needs at least one row");
+        let width = rows[0].len();
+        assert!(width > 0, "a lookup table needs at least one column");
+        assert!(rows.iter().all(|row| row.len() == width), "table rows differ in width");
+        LookupTable { rows }
+    }
+
+    /// `len` rows generated by `f(0), .., f(len - 1)`
+    pub fn from_fn(len: usize, f: impl FnMut(usize) -> Vec<F>) -> Self {
+        Self::from_rows((0..len).map(f).collect())
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &Vec<F>> + Clone {
+        self.rows.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows[0].len()
     }
 }
 
+impl<F: FieldExt> Default for LookupTable<F> {
+    /// The original demo table `{1..9}`
+    fn default() -> Self {
+        Self::from_values((1..10).map(|i| F::from(i as u64)))
+    }
+}
+
+/// `(x, y, x * y)` for `x, y` in `1..=4`, the three column table of the tuple tests
+#[cfg(test)]
+pub(crate) fn mul_table<F: FieldExt>() -> LookupTable<F> {
+    LookupTable::from_fn(16, |i| {
+        let (x, y) = (i as u64 / 4 + 1, i as u64 % 4 + 1);
+        vec![F::from(x), F::from(y), F::from(x * y)]
+    })
+}
+
+/// Picks the `LookupStrategy` of `MyCircuit` at configure time, `HARDENED` selects the
+/// tagged lookup that ignores table rows the chip never loaded
+pub trait StrategyChoice: Default {
+    const STRATEGY: LookupStrategy;
+    const HARDENED: bool;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TableColumnLookup<const HARDENED: bool = false>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AdviceLookup<const HARDENED: bool = false>;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedLookup<const HARDENED: bool = false>;
+
+/// Plain only, see `LookupStrategy::Instance`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InstanceLookup;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InstanceCopyLookup<const HARDENED: bool = false>;
+
+impl<const H: bool> StrategyChoice for TableColumnLookup<H> {
+    const STRATEGY: LookupStrategy = LookupStrategy::TableColumn;
+    const HARDENED: bool = H;
+}
+
+impl<const H: bool> StrategyChoice for AdviceLookup<H> {
+    const STRATEGY: LookupStrategy = LookupStrategy::Advice;
+    const HARDENED: bool = H;
+}
+
+impl<const H: bool> StrategyChoice for FixedLookup<H> {
+    const STRATEGY: LookupStrategy = LookupStrategy::Fixed;
+    const HARDENED: bool = H;
+}
+
+impl StrategyChoice for InstanceLookup {
+    const STRATEGY: LookupStrategy = LookupStrategy::Instance;
+    const HARDENED: bool = false;
+}
+
+impl<const H: bool> StrategyChoice for InstanceCopyLookup<H> {
+    const STRATEGY: LookupStrategy = LookupStrategy::InstanceCopy;
+    const HARDENED: bool = H;
+}
+
 #[derive(Default)]
-struct MyCircuit<F: FieldExt> {
+pub struct MyCircuit<F: FieldExt, S: StrategyChoice = AdviceLookup> {
+    a: Vec<Value<F>>,
+    table: LookupTable<F>,
+    _strategy: PhantomData<S>,
+}
+
+impl<F: FieldExt, S: StrategyChoice> MyCircuit<F, S> {
+    /// Looks `a` up in the default `{1..9}` table
+    pub fn new(a: Vec<Value<F>>) -> Self {
+        Self::with_table(a, LookupTable::default())
+    }
+
+    pub fn with_table(a: Vec<Value<F>>, table: LookupTable<F>) -> Self {
+        MyCircuit {
+            a,
+            table,
+            _strategy: PhantomData,
+        }
+    }
+
+    /// Number of looked up values
+    pub fn inputs(&self) -> usize {
+        self.a.len()
+    }
+
+    pub fn table(&self) -> &LookupTable<F> {
+        &self.table
+    }
+
+    /// Each region of the circuit with the rows it assigns: the inputs, then the table
+    fn regions(&self) -> (ConstraintSystem<F>, [(&'static str, usize); 2]) {
+        let mut cs = ConstraintSystem::default();
+        let config = Self::configure(&mut cs);
+        let regions = [("a,b", self.a.len()), (config.table_region(), config.table_rows(&self.table))];
+        (cs, regions)
+    }
+
+    /// Smallest `k` whose usable rows hold every region, after the blinding rows the lookups
+    /// and the other columns need
+    pub fn min_k(&self) -> u32 {
+        let (cs, regions) = self.regions();
+        regions.iter().map(|(_, rows)| rows_to_k(&cs, *rows)).max().unwrap()
+    }
+
+    /// Checks that every region fits in the usable rows of `k`, names the first one that does not
+    pub fn check_size(&self, k: u32) -> Result<(), RegionOverflow> {
+        let (cs, regions) = self.regions();
+        let usable_rows = usable_rows(k, &cs);
+        // the lookups need a few rows even when every region is empty
+        let minimum = cs.minimum_rows().saturating_sub(cs.blinding_factors() + 1);
+        for (region, rows) in regions {
+            let rows = rows.max(minimum);
+            if rows > usable_rows {
+                return Err(RegionOverflow {
+                    region,
+                    rows,
+                    k,
+                    usable_rows,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn builder() -> MyCircuitBuilder<F, S> {
+        MyCircuitBuilder {
+            a: vec![],
+            table: LookupTable::default(),
+            max_k: F::S,
+            _strategy: PhantomData,
+        }
+    }
+
+    /// Public inputs of the circuit: the table for the `Instance` and `InstanceCopy`
+    /// strategies, nothing for the others
+    pub fn instances(&self) -> Vec<Vec<F>> {
+        let mut cs = ConstraintSystem::default();
+        Self::configure(&mut cs).table_instance(&self.table)
+    }
+}
+
+/// Builds a `MyCircuit` together with the smallest `k` it fits in
+pub struct MyCircuitBuilder<F: FieldExt, S: StrategyChoice> {
     a: Vec<Value<F>>,
+    table: LookupTable<F>,
+    // largest `k` `build` may pick, the two-adicity of `F` unless set
+    max_k: u32,
+    _strategy: PhantomData<S>,
 }
 
-impl<F: FieldExt> Circuit<F> for MyCircuit<F> {
+impl<F: FieldExt, S: StrategyChoice> MyCircuitBuilder<F, S> {
+    /// Values looked up, none by default
+    pub fn inputs(mut self, a: Vec<Value<F>>) -> Self {
+        self.a = a;
+        self
+    }
+
+    /// The table, `{1..9}` by default
+    pub fn table(mut self, table: LookupTable<F>) -> Self {
+        self.table = table;
+        self
+    }
+
+    pub fn max_k(mut self, max_k: u32) -> Self {
+        self.max_k = max_k;
+        self
+    }
+
+    /// Returns the circuit and its minimum `k`, or the region that overflows `2^max_k` rows
+    pub fn build(self) -> Result<(MyCircuit<F, S>, u32), RegionOverflow> {
+        let circuit = MyCircuit::with_table(self.a, self.table);
+        let k = circuit.min_k();
+        if k > self.max_k {
+            circuit.check_size(self.max_k)?;
+        }
+        Ok((circuit, k))
+    }
+}
+
+impl<F: FieldExt, S: StrategyChoice> Circuit<F> for MyCircuit<F, S> {
     type Config = LookupConfig;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        MyCircuit::default()
+        // the table and the enabled selectors are part of the circuit, only the values of `a` are witness
+        Self::with_table(vec![Value::unknown(); self.a.len()], self.table.clone())
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        LookupChip::configure(meta)
+        LookupChip::configure(meta, 1, S::STRATEGY, S::HARDENED)
     }
 
-    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
-        let chip = LookupChip::<F>::construct(config);
-        chip.assign(layouter, &self.a)
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let mut chip = LookupChip::<F>::construct(config);
+        let a = self.a.iter().map(|v| vec![*v]).collect::<Vec<_>>();
+        chip.witness_lookup(layouter.namespace(|| "inputs"), &a)?;
+        chip.load_table(layouter.namespace(|| "table"), &self.table)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use halo2_proofs::{dev::MockProver, halo2curves::bn256::Fr as Fp};
+    use assert_matches::assert_matches;
+    use halo2_proofs::{
+        dev::{FailureLocation, MockProver, VerifyFailure},
+        halo2curves::bn256::Fr as Fp,
+    };
 
     use super::*;
+    use crate::prover::{self, MultiOpen};
     #[test]
     fn test_lookup_on_different_rows() {
         //here in the table there is no 0, so we expect the circuit will not pass. 
         // However, when using lookup_any, the unassigned table will be padded with zero, so it will pass
+        let a = [0, 1, 2, 3];
+        let a = a.map(|v| Value::known(Fp::from(v))).to_vec();
+
+        let (circuit, k) = MyCircuit::<Fp>::builder().inputs(a).build().unwrap();
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[test]
+    fn test_min_k() {
+        // the default table and its default row take the 10 usable rows of 2^4
+        let (_, k) = MyCircuit::<Fp>::builder().inputs(witness(&[0, 1, 2, 3])).build().unwrap();
+        assert_eq!(k, 4);
+
+        // 11 inputs overflow them, the input region sets k
+        let (circuit, k) = MyCircuit::<Fp>::builder().inputs(witness(&[1; 11])).build().unwrap();
+        assert_eq!(k, 5);
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+        assert_matches!(
+            MockProver::run(k - 1, &circuit, vec![]).map(|_| ()),
+            Err(Error::NotEnoughRowsAvailable { current_k: 4 })
+        );
+        assert_eq!(
+            circuit.check_size(4),
+            Err(RegionOverflow {
+                region: "a,b",
+                rows: 11,
+                k: 4,
+                usable_rows: 10,
+            })
+        );
+
+        // 30 table rows and the default row need 2^6 rows, whatever column holds them
+        let table = LookupTable::from_fn(30, |i| vec![Fp::from(i as u64 + 1)]);
+        let (circuit, k) = MyCircuit::<Fp, AdviceLookup<true>>::builder()
+            .inputs(witness(&[1, 30]))
+            .table(table.clone())
+            .build()
+            .unwrap();
+        assert_eq!(k, 6);
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+        assert_eq!(circuit.check_size(5).unwrap_err().region, "t2");
+
+        let circuit = MyCircuit::<Fp, TableColumnLookup<true>>::with_table(witness(&[1]), table);
+        assert_eq!(circuit.min_k(), 6);
+        assert_eq!(circuit.check_size(5).unwrap_err().region, "t1");
+    }
+
+    #[test]
+    fn test_builder_overflow() {
+        let table = LookupTable::from_fn(100, |i| vec![Fp::from(i as u64)]);
+        let overflow = MyCircuit::<Fp, FixedLookup>::builder()
+            .table(table.clone())
+            .max_k(6)
+            .build()
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(
+            overflow.to_string(),
+            "region `t3` needs 101 rows, k = 6 only has 58 usable rows"
+        );
+        assert_matches!(Error::from(overflow), Error::NotEnoughRowsAvailable { current_k: 6 });
+
+        let (_, k) = MyCircuit::<Fp, FixedLookup>::builder().table(table).max_k(7).build().unwrap();
+        assert_eq!(k, 7);
+    }
+
+    #[test]
+    fn test_hardened_lookup_rejects_padding() {
+        // same witness as above, but the table side is gated by `q_t`,
+        // so the zero padding of `t2` is no longer a table entry
         let k = 5;
         let a = [0, 1, 2, 3];
         let a = a.map(|v| Value::known(Fp::from(v))).to_vec();
 
-        let circuit = MyCircuit { a };
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::new(a);
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        let a = [1, 2, 3, 9];
+        let a = a.map(|v| Value::known(Fp::from(v))).to_vec();
+
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::new(a);
         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
         prover.assert_satisfied();
     }
-}
 
+    /// `(a, b, c)` against the three column table `{(x, y, x * y) | x, y in 1..=4}`,
+    /// both the plain and the hardened lookup
+    struct TupleCircuit<const HARDENED: bool> {
+        a: Vec<Vec<Value<Fp>>>,
+    }
+
+    impl<const HARDENED: bool> Circuit<Fp> for TupleCircuit<HARDENED> {
+        type Config = LookupConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            TupleCircuit {
+                a: vec![vec![Value::unknown(); 3]; self.a.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LookupChip::configure(meta, 3, LookupStrategy::Advice, HARDENED)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let mut chip = LookupChip::<Fp>::construct(config);
+            chip.witness_lookup(layouter.namespace(|| "inputs"), &self.a)?;
+            chip.load_table(layouter.namespace(|| "table"), &mul_table())
+        }
+    }
+
+    fn tuples(rows: &[[u64; 3]]) -> Vec<Vec<Value<Fp>>> {
+        rows.iter()
+            .map(|row| row.iter().map(|v| Value::known(Fp::from(*v))).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_tuple_lookup() {
+        let k = 5;
+
+        let circuit = TupleCircuit::<false> { a: tuples(&[[1, 1, 1], [2, 3, 6], [4, 4, 16]]) };
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+        let circuit = TupleCircuit::<true> { a: tuples(&[[1, 1, 1], [2, 3, 6], [4, 4, 16]]) };
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+        // every value appears in its column, but the tuple (2, 3, 4) is not a row of the table
+        let circuit = TupleCircuit::<false> { a: tuples(&[[2, 3, 4]]) };
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+
+        // the all-zero tuple only lives in the padding
+        let circuit = TupleCircuit::<false> { a: tuples(&[[0, 0, 0]]) };
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+        let circuit = TupleCircuit::<true> { a: tuples(&[[0, 0, 0]]) };
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+    }
+
+    /// `TupleCircuit` against the `TableColumn` table `t1`
+    struct TableColumnTupleCircuit {
+        a: Vec<Vec<Value<Fp>>>,
+    }
+
+    impl Circuit<Fp> for TableColumnTupleCircuit {
+        type Config = LookupConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            TableColumnTupleCircuit {
+                a: vec![vec![Value::unknown(); 3]; self.a.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LookupChip::configure(meta, 3, LookupStrategy::TableColumn, false)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let mut chip = LookupChip::<Fp>::construct(config);
+            chip.witness_lookup(layouter.namespace(|| "inputs"), &self.a)?;
+            chip.load_table(layouter.namespace(|| "table"), &mul_table())
+        }
+    }
+
+    #[test]
+    fn test_table_column_tuple_lookup() {
+        let k = 5;
+
+        let circuit = TableColumnTupleCircuit { a: tuples(&[[1, 1, 1], [2, 3, 6], [4, 4, 16]]) };
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+        let circuit = TableColumnTupleCircuit { a: tuples(&[[2, 3, 4]]) };
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+
+        // unassigned `t1` rows repeat its first row, the all-zero tuple is no table entry
+        let circuit = TableColumnTupleCircuit { a: tuples(&[[0, 0, 0]]) };
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn test_tuple_width_mismatch() {
+        let circuit = TupleCircuit::<false> { a: vec![vec![Value::known(Fp::one()); 2]] };
+        assert_matches!(MockProver::run(5, &circuit, vec![]).map(|_| ()), Err(Error::Synthesis));
+    }
+
+    #[test]
+    fn test_runtime_table() {
+        let k = 6;
+        let squares = LookupTable::from_values((0..20u64).map(|i| Fp::from(i * i)));
+
+        let a = [0, 1, 49, 361].map(|v| Value::known(Fp::from(v))).to_vec();
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::with_table(a, squares.clone());
+        circuit.check_size(k).unwrap();
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+        let a = [2].map(|v| Value::known(Fp::from(v))).to_vec();
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::with_table(a, squares);
+        assert!(MockProver::run(k, &circuit, vec![]).unwrap().verify().is_err());
+
+        // 40 rows plus the default row do not fit in the 2^5 rows of k = 5
+        let big = LookupTable::from_fn(40, |i| vec![Fp::from(i as u64)]);
+        let circuit = MyCircuit::<Fp>::with_table(vec![], big);
+        assert_matches!(circuit.check_size(5), Err(RegionOverflow { region: "t2", k: 5, .. }));
+        assert!(MockProver::run(5, &circuit, vec![]).is_err());
+        circuit.check_size(6).unwrap();
+    }
+
+    // negative scenarios: each one pins the exact failure `MockProver::verify` reports
+
+    fn witness(a: &[u64]) -> Vec<Value<Fp>> {
+        a.iter().map(|v| Value::known(Fp::from(*v))).collect()
+    }
+
+    /// Asserts a single `lookup_any` failure for the input at `offset` of the "a,b" region
+    fn assert_lookup_failure_at(failure: &VerifyFailure, offset: usize) {
+        assert_matches!(
+            failure,
+            VerifyFailure::Lookup {
+                name,
+                lookup_index: 0,
+                location: FailureLocation::InRegion { region, offset: o },
+            } if name == "lookup_any" && *region == (0, "a,b").into() && *o == offset
+        );
+    }
+
+    #[test]
+    fn test_value_outside_table() {
+        let circuit = MyCircuit::<Fp>::new(witness(&[1, 10, 2, 42]));
+        let failures = MockProver::run(5, &circuit, vec![]).unwrap().verify().unwrap_err();
+        assert_eq!(failures.len(), 2);
+        assert_lookup_failure_at(&failures[0], 1);
+        assert_lookup_failure_at(&failures[1], 3);
+    }
+
+    #[test]
+    fn test_padding_value_in_hardened_table() {
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::new(witness(&[1, 2, 0]));
+        let failures = MockProver::run(5, &circuit, vec![]).unwrap().verify().unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_lookup_failure_at(&failures[0], 2);
+    }
+
+    /// Runs the chip on `a`, then writes `unchecked` into `a` in another region without
+    /// enabling the selector
+    struct DisabledRowCircuit {
+        a: Vec<Value<Fp>>,
+        unchecked: Vec<Value<Fp>>,
+    }
+
+    impl Circuit<Fp> for DisabledRowCircuit {
+        type Config = LookupConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            DisabledRowCircuit {
+                a: vec![Value::unknown(); self.a.len()],
+                unchecked: vec![Value::unknown(); self.unchecked.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LookupChip::configure(meta, 1, LookupStrategy::Advice, false)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let a = self.a.iter().map(|v| vec![*v]).collect::<Vec<_>>();
+            let mut chip = LookupChip::<Fp>::construct(config.clone());
+            chip.witness_lookup(layouter.namespace(|| "inputs"), &a)?;
+            chip.load_table(layouter.namespace(|| "table"), &LookupTable::default())?;
+            layouter.assign_region(
+                || "unchecked",
+                |mut region| {
+                    for (i, value) in self.unchecked.iter().enumerate() {
+                        region.assign_advice(|| "a col", config.a[0], i, || *value)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_disabled_selector_with_bad_value() {
+        // a disabled row looks up the default tuple, whatever `a` holds
+        let circuit = DisabledRowCircuit {
+            a: witness(&[2]),
+            unchecked: witness(&[10, 42]),
+        };
+        MockProver::run(5, &circuit, vec![]).unwrap().assert_satisfied();
+
+        // so only the enabled bad value is reported
+        let circuit = DisabledRowCircuit {
+            a: witness(&[2, 11]),
+            unchecked: witness(&[10, 42]),
+        };
+        let failures = MockProver::run(5, &circuit, vec![]).unwrap().verify().unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_lookup_failure_at(&failures[0], 1);
+    }
+
+    #[test]
+    fn test_table_too_big_for_k() {
+        // k = 4 leaves 16 - (blinding factors + 1) usable rows, fewer than the 20 table rows
+        let table = LookupTable::from_fn(19, |i| vec![Fp::from(i as u64 + 1)]);
+        let circuit = MyCircuit::<Fp>::with_table(witness(&[1]), table);
+        assert_matches!(circuit.check_size(4), Err(RegionOverflow { region: "t2", rows: 20, k: 4, .. }));
+        assert_matches!(
+            MockProver::run(4, &circuit, vec![]).map(|_| ()),
+            Err(Error::NotEnoughRowsAvailable { current_k: 4 })
+        );
+        MockProver::run(5, &circuit, vec![]).unwrap().assert_satisfied();
+    }
+
+    // the same witnesses against every strategy
+
+    fn verifies<S: StrategyChoice>(a: &[u64], table: LookupTable<Fp>) -> bool {
+        let circuit = MyCircuit::<Fp, S>::with_table(witness(a), table);
+        MockProver::run(5, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_strategy_padding() {
+        // 0 is never loaded. The TableColumn padding repeats the default row (1),
+        // the advice and fixed padding is zero, so only the plain lookup_any strategies accept it
+        let table = LookupTable::default;
+        assert!(!verifies::<TableColumnLookup>(&[0, 1], table()));
+        assert!(verifies::<AdviceLookup>(&[0, 1], table()));
+        assert!(verifies::<FixedLookup>(&[0, 1], table()));
+        assert!(!verifies::<TableColumnLookup<true>>(&[0, 1], table()));
+        assert!(!verifies::<AdviceLookup<true>>(&[0, 1], table()));
+        assert!(!verifies::<FixedLookup<true>>(&[0, 1], table()));
+
+        // 1 is not in `{2, 3}` either, but every plain strategy loads the default tuple (1)
+        let table = || LookupTable::from_values([Fp::from(2), Fp::from(3)]);
+        assert!(verifies::<TableColumnLookup>(&[1, 2], table()));
+        assert!(verifies::<AdviceLookup>(&[1, 2], table()));
+        assert!(verifies::<FixedLookup>(&[1, 2], table()));
+        assert!(!verifies::<TableColumnLookup<true>>(&[1, 2], table()));
+        assert!(!verifies::<AdviceLookup<true>>(&[1, 2], table()));
+        assert!(!verifies::<FixedLookup<true>>(&[1, 2], table()));
+    }
+
+    #[test]
+    fn test_strategy_soundness() {
+        let table = LookupTable::default;
+        assert!(verifies::<TableColumnLookup>(&[1, 5, 9], table()));
+        assert!(verifies::<AdviceLookup>(&[1, 5, 9], table()));
+        assert!(verifies::<FixedLookup>(&[1, 5, 9], table()));
+        assert!(verifies::<TableColumnLookup<true>>(&[1, 5, 9], table()));
+        assert!(verifies::<AdviceLookup<true>>(&[1, 5, 9], table()));
+        assert!(verifies::<FixedLookup<true>>(&[1, 5, 9], table()));
+
+        assert!(!verifies::<TableColumnLookup>(&[1, 10], table()));
+        assert!(!verifies::<AdviceLookup>(&[1, 10], table()));
+        assert!(!verifies::<FixedLookup>(&[1, 10], table()));
+        assert!(!verifies::<TableColumnLookup<true>>(&[1, 10], table()));
+        assert!(!verifies::<AdviceLookup<true>>(&[1, 10], table()));
+        assert!(!verifies::<FixedLookup<true>>(&[1, 10], table()));
+
+        // MockProver checks the table the circuit loads, a verifier only knows the vk. A
+        // prover who replaces 1 by 10 in the advice table proves 10 against the vk of `{1..9}`.
+        assert!(other_table_verifies::<AdviceLookup>());
+        assert!(other_table_verifies::<AdviceLookup<true>>());
+        assert!(!other_table_verifies::<TableColumnLookup>());
+        assert!(!other_table_verifies::<TableColumnLookup<true>>());
+        assert!(!other_table_verifies::<FixedLookup>());
+        assert!(!other_table_verifies::<FixedLookup<true>>());
+    }
+
+    /// Proves `[10]` with the table `{10, 2..9}`, verifies with the vk of the default table
+    fn other_table_verifies<S: StrategyChoice>() -> bool {
+        let params = prover::setup(5);
+        let honest = MyCircuit::<Fp, S>::with_table(witness(&[1]), LookupTable::default());
+        let vk = keygen_vk(&params, &honest).unwrap();
+
+        let table = LookupTable::from_values((1..10u64).map(|i| Fp::from(if i == 1 { 10 } else { i })));
+        let circuit = MyCircuit::<Fp, S>::with_table(witness(&[10]), table);
+        let pk = prover::keygen(&params, &circuit).unwrap();
+        let proof = prover::prove(&params, &pk, circuit, &[], MultiOpen::Shplonk).unwrap();
+        prover::verify(&params, &vk, &proof, &[], MultiOpen::Shplonk).is_ok()
+    }
+
+    /// (advice columns, fixed columns, selectors, lookups) of a configured `MyCircuit`
+    fn shape<S: StrategyChoice>() -> (usize, usize, usize, usize) {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        MyCircuit::<Fp, S>::configure(&mut cs);
+        (cs.num_advice_columns(), cs.num_fixed_columns(), cs.num_selectors(), cs.lookups().len())
+    }
+
+    #[test]
+    fn test_strategy_cost() {
+        // `a` plus the table column in advice, TableColumns are fixed columns
+        assert_eq!(shape::<TableColumnLookup>(), (1, 1, 1, 1));
+        assert_eq!(shape::<AdviceLookup>(), (2, 0, 1, 1));
+        assert_eq!(shape::<FixedLookup>(), (1, 1, 1, 1));
+        // hardening costs one tag column (or selector) and one more lookup tuple entry
+        assert_eq!(shape::<TableColumnLookup<true>>(), (1, 2, 1, 1));
+        assert_eq!(shape::<AdviceLookup<true>>(), (2, 0, 2, 1));
+        assert_eq!(shape::<FixedLookup<true>>(), (1, 1, 2, 1));
+        // a public table adds instance columns, and its copy the advice columns of `t2`
+        assert_eq!(shape::<InstanceLookup>(), (1, 0, 1, 1));
+        assert_eq!(shape::<InstanceCopyLookup<true>>(), (2, 0, 2, 1));
+    }
+
+    /// Cells of another column, copied into the lookup, the copies are exposed as public inputs
+    struct CopyCircuit {
+        b: Vec<Value<Fp>>,
+    }
+
+    impl Circuit<Fp> for CopyCircuit {
+        type Config = (LookupConfig, Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            CopyCircuit {
+                b: vec![Value::unknown(); self.b.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let b = meta.advice_column();
+            let instance = meta.instance_column();
+            meta.enable_equality(b);
+            meta.enable_equality(instance);
+            (LookupChip::configure(meta, 1, LookupStrategy::Advice, true), b, instance)
+        }
+
+        fn synthesize(&self, (config, b, instance): Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let cells = layouter.assign_region(
+                || "b",
+                |mut region| {
+                    self.b
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| Ok(vec![region.assign_advice(|| "b", b, i, || *v)?]))
+                        .collect::<Result<Vec<_>, Error>>()
+                },
+            )?;
+
+            let mut chip = LookupChip::<Fp>::construct(config);
+            let copies = chip.copy_lookup(layouter.namespace(|| "copy"), &cells)?;
+            chip.load_table(layouter.namespace(|| "table"), &LookupTable::default())?;
+            for (i, copy) in copies.iter().enumerate() {
+                layouter.constrain_instance(copy[0].cell(), instance, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_copy_lookup() {
+        let circuit = CopyCircuit { b: witness(&[3, 7]) };
+        let prover = MockProver::run(5, &circuit, vec![vec![Fp::from(3), Fp::from(7)]]).unwrap();
+        prover.assert_satisfied();
+
+        // the returned cells carry the copied values
+        let prover = MockProver::run(5, &circuit, vec![vec![Fp::from(3), Fp::from(8)]]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // the copy is looked up like a witnessed input
+        let circuit = CopyCircuit { b: witness(&[3, 10]) };
+        let prover = MockProver::run(5, &circuit, vec![vec![Fp::from(3), Fp::from(10)]]).unwrap();
+        let failures = prover.verify().unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_matches!(&failures[0], VerifyFailure::Lookup { name, .. } if name == "lookup_any");
+    }
+
+    /// Two callers witness lookups and a third copies cells, all against one loaded table
+    struct SharedTableCircuit {
+        callers: Vec<Vec<Value<Fp>>>,
+        load_twice: bool,
+    }
+
+    impl Circuit<Fp> for SharedTableCircuit {
+        type Config = LookupConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            SharedTableCircuit {
+                callers: self.callers.iter().map(|a| vec![Value::unknown(); a.len()]).collect(),
+                load_twice: self.load_twice,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LookupChip::configure(meta, 1, LookupStrategy::Advice, true)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let mut chip = LookupChip::<Fp>::construct(config);
+            chip.load_table(layouter.namespace(|| "table"), &LookupTable::default())?;
+            if self.load_twice {
+                chip.load_table(layouter.namespace(|| "table again"), &LookupTable::default())?;
+            }
+            assert!(chip.loaded().is_some());
+
+            let mut cells = vec![];
+            for a in self.callers.iter() {
+                let tuples = a.iter().map(|v| vec![*v]).collect::<Vec<_>>();
+                cells.extend(chip.witness_lookup(layouter.namespace(|| "caller"), &tuples)?);
+            }
+            chip.copy_lookup(layouter.namespace(|| "copies"), &cells)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_shared_table() {
+        let circuit = SharedTableCircuit {
+            callers: vec![witness(&[1, 2]), witness(&[9]), witness(&[5, 6, 7])],
+            load_twice: false,
+        };
+        MockProver::run(5, &circuit, vec![]).unwrap().assert_satisfied();
+
+        let circuit = SharedTableCircuit {
+            callers: vec![witness(&[1, 2]), witness(&[10])],
+            load_twice: false,
+        };
+        assert!(MockProver::run(5, &circuit, vec![]).unwrap().verify().is_err());
+
+        let circuit = SharedTableCircuit {
+            callers: vec![witness(&[1])],
+            load_twice: true,
+        };
+        assert_matches!(MockProver::run(5, &circuit, vec![]).map(|_| ()), Err(Error::Synthesis));
+    }
+
+    /// `(a[i - 1], a[i])` looked up in `(t[j], t[j + 1])`: pairs of consecutive table rows
+    fn successor_rotations() -> LookupRotations {
+        LookupRotations::new(
+            vec![(0, Rotation::prev()), (0, Rotation::cur())],
+            vec![(0, Rotation::cur()), (0, Rotation::next())],
+        )
+    }
+
+    fn successors() -> LookupTable<Fp> {
+        LookupTable::from_values((1..=9).map(Fp::from))
+    }
+
+    /// Witnesses each entry of `regions` in its own region against a successor table
+    struct RotatedCircuit<S: StrategyChoice> {
+        regions: Vec<Vec<Value<Fp>>>,
+        table: LookupTable<Fp>,
+        _strategy: PhantomData<S>,
+    }
+
+    impl<S: StrategyChoice> RotatedCircuit<S> {
+        fn new(regions: Vec<Vec<u64>>, table: LookupTable<Fp>) -> Self {
+            RotatedCircuit {
+                regions: regions
+                    .iter()
+                    .map(|region| region.iter().map(|v| Value::known(Fp::from(*v))).collect())
+                    .collect(),
+                table,
+                _strategy: PhantomData,
+            }
+        }
+    }
+
+    impl<S: StrategyChoice> Circuit<Fp> for RotatedCircuit<S> {
+        type Config = LookupConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            RotatedCircuit {
+                regions: self.regions.iter().map(|region| vec![Value::unknown(); region.len()]).collect(),
+                table: self.table.clone(),
+                _strategy: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            LookupChip::configure_with_rotations(meta, successor_rotations(), S::STRATEGY, S::HARDENED)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let mut chip = LookupChip::<Fp>::construct(config);
+            for region in self.regions.iter() {
+                let rows = region.iter().map(|v| vec![*v]).collect::<Vec<_>>();
+                chip.witness_lookup(layouter.namespace(|| "inputs"), &rows)?;
+            }
+            chip.load_table(layouter.namespace(|| "table"), &self.table)
+        }
+    }
+
+    fn rotated_verifies<S: StrategyChoice>(regions: &[&[u64]], table: LookupTable<Fp>) -> bool {
+        let circuit = RotatedCircuit::<S>::new(regions.iter().map(|r| r.to_vec()).collect(), table);
+        MockProver::run(5, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    fn check_successors<S: StrategyChoice>() {
+        assert!(rotated_verifies::<S>(&[&[3, 4, 5, 6]], successors()));
+        assert!(!rotated_verifies::<S>(&[&[3, 5]], successors()));
+        // (4, 3) are consecutive table rows, but in the wrong order
+        assert!(!rotated_verifies::<S>(&[&[4, 3]], successors()));
+    }
+
+    #[test]
+    fn test_rotated_lookup() {
+        check_successors::<AdviceLookup>();
+        check_successors::<AdviceLookup<true>>();
+        check_successors::<FixedLookup>();
+        check_successors::<FixedLookup<true>>();
+    }
+
+    #[test]
+    fn test_rotation_default_rows() {
+        // 1 is not in `{5..9}`, but the last default row of ones is followed by 5
+        let table = || LookupTable::from_values((5..=9).map(Fp::from));
+        assert!(rotated_verifies::<AdviceLookup>(&[&[1, 5]], table()));
+        assert!(rotated_verifies::<FixedLookup>(&[&[1, 5]], table()));
+        assert!(!rotated_verifies::<AdviceLookup<true>>(&[&[1, 5]], table()));
+        assert!(!rotated_verifies::<FixedLookup<true>>(&[&[1, 5]], table()));
+        assert!(rotated_verifies::<AdviceLookup<true>>(&[&[5, 6]], table()));
+    }
+
+    #[test]
+    fn test_rotation_region_edges() {
+        // (6, 2) and (4, 8) are not successors, but the first row of a region has no previous
+        // row in it, so it is not looked up together with the last row of the region above
+        assert!(rotated_verifies::<AdviceLookup<true>>(&[&[5, 6], &[2, 3, 4], &[8]], successors()));
+        assert!(!rotated_verifies::<AdviceLookup<true>>(&[&[5, 6, 2, 3, 4]], successors()));
+    }
+
+    #[test]
+    fn test_rotation_table_edge() {
+        // the last table row is read with the zero padding below it as its successor
+        assert!(rotated_verifies::<AdviceLookup>(&[&[9, 0]], successors()));
+        assert!(rotated_verifies::<FixedLookup>(&[&[9, 0]], successors()));
+        // `q_t` is only enabled on the rows whose successor is a table row too
+        assert!(!rotated_verifies::<AdviceLookup<true>>(&[&[9, 0]], successors()));
+        assert!(!rotated_verifies::<FixedLookup<true>>(&[&[9, 0]], successors()));
+    }
+
+    #[test]
+    fn test_rotated_table_fits() {
+        let k = 5;
+        let mut cs = ConstraintSystem::<Fp>::default();
+        let config = RotatedCircuit::<AdviceLookup>::configure(&mut cs);
+        let usable_rows = (1 << k) - (cs.blinding_factors() + 1);
+
+        // the successor of the default row is a default row too, so two of them come first
+        let table = LookupTable::from_fn(usable_rows - 1, |i| vec![Fp::from(i as u64 + 1)]);
+        assert_eq!(config.table_rows(&table), usable_rows + 1);
+        assert_matches!(
+            config.check_fits(&table, k, &cs),
+            Err(Error::NotEnoughRowsAvailable { current_k: 5 })
+        );
+        let circuit = RotatedCircuit::<AdviceLookup>::new(vec![], table);
+        assert_matches!(
+            MockProver::run(k, &circuit, vec![]).map(|_| ()),
+            Err(Error::NotEnoughRowsAvailable { .. })
+        );
+
+        let table = LookupTable::from_fn(usable_rows - 2, |i| vec![Fp::from(i as u64 + 1)]);
+        config.check_fits(&table, k, &cs).unwrap();
+    }
+
+    fn check_boundary_rows<S: StrategyChoice>() {
+        let k = 5;
+        let mut cs = ConstraintSystem::<Fp>::default();
+        RotatedCircuit::<S>::configure(&mut cs);
+        let usable_rows = (1 << k) - (cs.blinding_factors() + 1);
+        let table = || LookupTable::from_values([Fp::from(5); 2]);
+
+        // the region fills every usable row from row 0, whose previous row wraps around to the
+        // blinding rows at the end of the column, so row 0 is not looked up
+        let circuit = RotatedCircuit::<S>::new(vec![vec![5; usable_rows]], table());
+        MockProver::run(k, &circuit, vec![]).unwrap().assert_satisfied();
+
+        // and no input can be placed on a blinding row
+        let circuit = RotatedCircuit::<S>::new(vec![vec![5; usable_rows + 1]], table());
+        assert_matches!(
+            MockProver::run(k, &circuit, vec![]).map(|_| ()),
+            Err(Error::NotEnoughRowsAvailable { .. })
+        );
+    }
+
+    #[test]
+    fn test_rotation_boundary_rows() {
+        check_boundary_rows::<AdviceLookup>();
+        check_boundary_rows::<AdviceLookup<true>>();
+    }
+
+    /// Runs `circuit` against the public table `instance`, `Err` if synthesis fails
+    fn public_table_verifies<S: StrategyChoice>(a: &[u64], instance: Vec<Vec<Fp>>) -> Result<bool, Error> {
+        let circuit = MyCircuit::<Fp, S>::new(witness(a));
+        Ok(MockProver::run(5, &circuit, instance)?.verify().is_ok())
+    }
+
+    fn check_public_table<S: StrategyChoice>() {
+        let instance = MyCircuit::<Fp, S>::new(vec![]).instances();
+        // the default row and {1..9}
+        assert_eq!(instance, vec![(0..10).map(|i| Fp::from(i.max(1))).collect::<Vec<_>>()]);
+
+        assert!(public_table_verifies::<S>(&[1, 2, 9], instance.clone()).unwrap());
+        assert!(!public_table_verifies::<S>(&[1, 10], instance.clone()).unwrap());
+        // the rows past the public inputs are zero
+        assert_eq!(public_table_verifies::<S>(&[0], instance).unwrap(), !S::HARDENED);
+    }
+
+    #[test]
+    fn test_public_table() {
+        check_public_table::<InstanceLookup>();
+        check_public_table::<InstanceCopyLookup>();
+        check_public_table::<InstanceCopyLookup<true>>();
+    }
+
+    #[test]
+    fn test_public_table_length() {
+        let instance = MyCircuit::<Fp, InstanceLookup>::new(vec![]).instances();
+        let mut longer = instance.clone();
+        longer[0].push(Fp::from(10));
+        let mut shorter = instance;
+        shorter[0].pop();
+
+        // queried directly, every public row is a table row
+        assert!(public_table_verifies::<InstanceLookup>(&[10], longer.clone()).unwrap());
+
+        // copied, the public inputs must hold the table of the circuit, the copy does not
+        // extend past it
+        assert!(!public_table_verifies::<InstanceCopyLookup>(&[10], longer).unwrap());
+        assert_matches!(
+            public_table_verifies::<InstanceCopyLookup>(&[1], shorter),
+            Err(Error::Synthesis)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "use InstanceCopy")]
+    fn test_hardened_instance_refused() {
+        LookupChip::configure(&mut ConstraintSystem::<Fp>::default(), 1, LookupStrategy::Instance, true);
+    }
+}
 
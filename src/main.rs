@@ -1,4 +1,248 @@
-mod lookup_padding;
-fn main(){
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
 
-}
\ No newline at end of file
+use halo2_proofs::{
+    dev::MockProver,
+    halo2curves::bn256::Fr,
+    SerdeFormat,
+};
+
+#[cfg(feature = "dev-graph")]
+use lookup_test::layout;
+use lookup_test::{
+    artifact::{self, ArtifactHeader},
+    audit,
+    cost::{compare_vector_lookups, cost_grid, CostReport, VectorCost},
+    prover::{self, MultiOpen},
+    scenario::Scenario,
+    AdviceLookup, FixedLookup, InstanceCopyLookup, InstanceLookup, LookupStrategy, StrategyChoice,
+    TableColumnLookup,
+};
+
+const USAGE: &str = "usage: lookup_test <mock|audit|keygen|prove|verify|cost|cost-grid|cost-compressed|layout> [options]
+
+options:
+    --input <file>       JSON scenario with the witness, table, strategy, hardened and k,
+                         the options after it override its fields (see the scenario module)
+    --k <k>              circuit size is 2^k rows (default: the smallest k the circuit fits in)
+    --witness <a,b,..>   comma separated values looked up in the table (default 0,1,2,3)
+    --strategy <s>       table, advice, fixed, instance or instance-copy: the kind of columns
+                         holding the table (default advice)
+    --hardened           only count table rows the chip loaded, not the padding
+    --gwc                prove and verify with GWC instead of SHPLONK
+    --processed          keygen writes params and vk with compressed points, checked on read
+    --dir <dir>          where keygen/prove/verify read and write artifacts (default ./artifacts)
+
+keygen writes params.bin and vk.bin, prove reads params.bin and writes proof.bin,
+verify reads all three. The circuit shape depends on the number of witness values,
+so keygen, prove and verify must be given the same witness length. Each file starts
+with a header naming its circuit, k, strategy and table, loading the artifacts of
+another circuit fails with the field that differs. With the instance
+strategies the table is public, mock/prove/verify pass it as the public inputs.
+audit reports, for each lookup on advice or instance columns, the table tuples that
+only the zero padding of unassigned rows adds.
+cost reports the columns, lookups, degree and proof size of the circuit, cost-grid
+sweeps every strategy over input lengths and table sizes at their minimum k, and
+cost-compressed compares wide lookups with and without challenge compression.
+layout writes layout.png and layout.dot (needs the dev-graph feature).";
+
+struct Options {
+    command: String,
+    scenario: Scenario,
+    scheme: MultiOpen,
+    format: SerdeFormat,
+    dir: PathBuf,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let command = args.next().ok_or("missing command")?;
+        let mut options = Options {
+            command,
+            scenario: Scenario::default(),
+            scheme: MultiOpen::Shplonk,
+            format: SerdeFormat::RawBytes,
+            dir: PathBuf::from("artifacts"),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+            match arg.as_str() {
+                "--input" => {
+                    let path = value()?;
+                    options.scenario =
+                        Scenario::load(Path::new(&path)).map_err(|e| format!("invalid --input {}: {}", path, e))?
+                }
+                "--k" => options.scenario.k = Some(value()?.parse().map_err(|e| format!("invalid --k: {}", e))?),
+                "--witness" => {
+                    options.scenario.witness = value()?
+                        .split(',')
+                        .filter(|v| !v.is_empty())
+                        .map(|v| v.trim().parse::<u64>().map(Fr::from))
+                        .collect::<Result<_, _>>()
+                        .map_err(|e| format!("invalid --witness: {}", e))?
+                }
+                "--strategy" => options.scenario.strategy = value()?.parse()?,
+                "--hardened" => options.scenario.hardened = true,
+                "--gwc" => options.scheme = MultiOpen::Gwc,
+                "--processed" => options.format = SerdeFormat::Processed,
+                "--dir" => options.dir = PathBuf::from(value()?),
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn run<S: StrategyChoice>(options: &Options) -> Result<(), Box<dyn Error>> {
+    let (circuit, k) = options.scenario.circuit::<S>()?;
+    let instances = circuit.instances();
+    let instance_refs = instances.iter().map(|column| column.as_slice()).collect::<Vec<_>>();
+    let header = ArtifactHeader::new(&circuit, k);
+
+    let params_path = options.dir.join("params.bin");
+    let vk_path = options.dir.join("vk.bin");
+    let proof_path = options.dir.join("proof.bin");
+
+    match options.command.as_str() {
+        "mock" => {
+            let prover = MockProver::run(k, &circuit, instances.clone())?;
+            match prover.verify() {
+                Ok(()) => println!("mock prover: satisfied"),
+                Err(failures) => {
+                    for failure in failures.iter() {
+                        println!("{}", failure);
+                    }
+                    return Err(format!("mock prover: {} failure(s)", failures.len()).into());
+                }
+            }
+        }
+        "audit" => {
+            let reports = audit::audit_lookups(k, &circuit, instances.clone())?;
+            if reports.is_empty() {
+                println!("no lookup table queries advice or instance columns");
+            }
+            for report in reports.iter() {
+                print!("{}", report);
+            }
+        }
+        "keygen" => {
+            fs::create_dir_all(&options.dir)?;
+            let params = prover::setup(k);
+            let pk = prover::keygen(&params, &circuit)?;
+            artifact::save_params(&params_path, &header, &params, options.format)?;
+            artifact::save_vk(&vk_path, &header, pk.get_vk(), options.format)?;
+            println!("wrote {} and {}", params_path.display(), vk_path.display());
+        }
+        "prove" => {
+            let params = artifact::load_params(&params_path, k)?;
+            let pk = prover::keygen(&params, &circuit)?;
+            let proof = prover::prove(&params, &pk, circuit, &instance_refs, options.scheme)?;
+            artifact::save_proof(&proof_path, &header, &proof, options.scheme)?;
+            println!("wrote {} ({} bytes)", proof_path.display(), proof.len());
+        }
+        "verify" => {
+            let params = artifact::load_params(&params_path, k)?;
+            let vk = artifact::load_vk::<S>(&vk_path, &header)?;
+            let proof = artifact::load_proof(&proof_path, &header, options.scheme)?;
+            prover::verify(&params, &vk, &proof, &instance_refs, options.scheme)?;
+            println!("proof verified");
+        }
+        "cost" => {
+            println!("{}", CostReport::HEADER);
+            println!("{}", CostReport::measure(k, &circuit));
+        }
+        #[cfg(feature = "dev-graph")]
+        "layout" => {
+            fs::create_dir_all(&options.dir)?;
+            let png = options.dir.join("layout.png");
+            let dot = options.dir.join("layout.dot");
+            layout::render_layout(k, &circuit, &png)?;
+            layout::render_dot_graph(&circuit, &dot)?;
+            println!("wrote {} and {}", png.display(), dot.display());
+        }
+        #[cfg(not(feature = "dev-graph"))]
+        "layout" => return Err("layout needs the dev-graph feature: cargo run --features dev-graph".into()),
+        command => return Err(format!("unknown command {}\n\n{}", command, USAGE).into()),
+    }
+    Ok(())
+}
+
+/// Inputs and table sizes swept by `cost-grid`
+const GRID_INPUTS: [usize; 3] = [4, 64, 1024];
+const GRID_TABLE_SIZES: [usize; 3] = [9, 256, 4096];
+
+fn print_cost_grid() {
+    println!("{}", CostReport::HEADER);
+    let reports = [
+        cost_grid::<TableColumnLookup>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<TableColumnLookup<true>>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<AdviceLookup>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<AdviceLookup<true>>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<FixedLookup>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<FixedLookup<true>>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<InstanceLookup>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<InstanceCopyLookup>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+        cost_grid::<InstanceCopyLookup<true>>(&GRID_INPUTS, &GRID_TABLE_SIZES),
+    ];
+    for report in reports.iter().flatten() {
+        println!("{}", report);
+    }
+}
+
+/// Inputs and table size of the `cost-compressed` comparison
+const VECTOR_INPUTS: usize = 64;
+const VECTOR_TABLE_SIZE: usize = 256;
+
+fn print_vector_costs() {
+    println!("{}", VectorCost::HEADER);
+    let reports = [
+        compare_vector_lookups::<2>(VECTOR_INPUTS, VECTOR_TABLE_SIZE),
+        compare_vector_lookups::<4>(VECTOR_INPUTS, VECTOR_TABLE_SIZE),
+        compare_vector_lookups::<8>(VECTOR_INPUTS, VECTOR_TABLE_SIZE),
+    ];
+    for report in reports.iter().flatten() {
+        println!("{}", report);
+    }
+}
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if options.command == "cost-grid" {
+        print_cost_grid();
+        return;
+    }
+    if options.command == "cost-compressed" {
+        print_vector_costs();
+        return;
+    }
+
+    let result = match (options.scenario.strategy, options.scenario.hardened) {
+        (LookupStrategy::TableColumn, false) => run::<TableColumnLookup>(&options),
+        (LookupStrategy::TableColumn, true) => run::<TableColumnLookup<true>>(&options),
+        (LookupStrategy::Advice, false) => run::<AdviceLookup>(&options),
+        (LookupStrategy::Advice, true) => run::<AdviceLookup<true>>(&options),
+        (LookupStrategy::Fixed, false) => run::<FixedLookup>(&options),
+        (LookupStrategy::Fixed, true) => run::<FixedLookup<true>>(&options),
+        (LookupStrategy::Instance, false) => run::<InstanceLookup>(&options),
+        (LookupStrategy::Instance, true) => Err("the instance table cannot be hardened, use instance-copy".into()),
+        (LookupStrategy::InstanceCopy, false) => run::<InstanceCopyLookup>(&options),
+        (LookupStrategy::InstanceCopy, true) => run::<InstanceCopyLookup<true>>(&options),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
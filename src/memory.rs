@@ -0,0 +1,405 @@
+//! Read/write memory consistency for an execution trace.
+//!
+//! Every access is a row `(addr, t, value, is_write)` of the trace, `t` counting the accesses
+//! from 0. The chip also assigns the accesses sorted by address, then timestamp, and proves:
+//!
+//! - the sorted copy is a permutation of the trace (`ShuffleChip`)
+//! - it is sorted: on consecutive rows with `same = 1` the address repeats and the timestamp
+//!   grows, with `same = 0` the address grows. The growth minus one is assigned in a `step`
+//!   column and range checked to 64 bits by a `RangeCheckChip`, in limbs of `b` bits, so
+//!   addresses can be anywhere in `u64`.
+//! - a read returns the value of the previous access to its address, or 0 for the first one
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+use crate::{
+    range_check::{RangeCheckChip, RangeCheckConfig},
+    shuffle::{ShuffleChip, ShuffleConfig, ShuffleInstructions},
+};
+
+/// Bits of the address and timestamp steps, addresses and timestamps are `u64`
+const STEP_BITS: usize = 64;
+
+/// One memory access of a trace
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAccess<F: FieldExt> {
+    pub addr: u64,
+    pub value: F,
+    pub is_write: bool,
+}
+
+impl<F: FieldExt> MemoryAccess<F> {
+    pub fn read(addr: u64, value: F) -> Self {
+        MemoryAccess {
+            addr,
+            value,
+            is_write: false,
+        }
+    }
+
+    pub fn write(addr: u64, value: F) -> Self {
+        MemoryAccess {
+            addr,
+            value,
+            is_write: true,
+        }
+    }
+
+    /// `(addr, t, value, is_write)` at timestamp `t`
+    fn row(&self, t: usize) -> [F; 4] {
+        [F::from(self.addr), F::from(t as u64), self.value, F::from(self.is_write as u64)]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryConfig {
+    // `(addr, t, value, is_write)` in execution order
+    trace: [Column<Advice>; 4],
+    // the same rows sorted by `(addr, t)`
+    sorted: [Column<Advice>; 4],
+    // 1 when a sorted row has the address of the row above it
+    same: Column<Advice>,
+    // the timestamp growth minus one when `same = 1`, the address growth minus one otherwise
+    step: Column<Advice>,
+    // `t = 0` on the first trace row, `t` counts up on the others
+    q_trace_first: Selector,
+    q_trace: Selector,
+    // the first sorted row, and every row that is compared to the one above it
+    q_sorted_first: Selector,
+    q_sorted: Selector,
+    range: RangeCheckConfig,
+    shuffle: ShuffleConfig,
+}
+
+pub struct MemoryChip<F: FieldExt> {
+    config: MemoryConfig,
+    range: RangeCheckChip<F>,
+    shuffle: ShuffleChip<F>,
+}
+
+impl<F: FieldExt> MemoryChip<F> {
+    pub fn construct(config: MemoryConfig) -> Self {
+        let range = RangeCheckChip::construct(config.range.clone());
+        let shuffle = ShuffleChip::construct(config.shuffle.clone());
+        MemoryChip { config, range, shuffle }
+    }
+
+    /// `limb_bits` is the limb width of the step range checks, their table has `2^limb_bits` rows
+    pub fn configure(meta: &mut ConstraintSystem<F>, limb_bits: usize) -> MemoryConfig {
+        let trace = [(); 4].map(|_| meta.advice_column());
+        let sorted = [(); 4].map(|_| meta.advice_column());
+        let same = meta.advice_column();
+        let step = meta.advice_column();
+        let q_trace_first = meta.selector();
+        let q_trace = meta.selector();
+        let q_sorted_first = meta.selector();
+        let q_sorted = meta.selector();
+        let range = RangeCheckChip::configure(meta, limb_bits);
+        let shuffle = ShuffleChip::configure(meta, 4);
+
+        for column in trace.iter().chain(sorted.iter()).chain([&step]) {
+            meta.enable_equality(*column);
+        }
+
+        meta.create_gate("trace timestamps", |meta| {
+            let q_first = meta.query_selector(q_trace_first);
+            let q = meta.query_selector(q_trace);
+            let t_prev = meta.query_advice(trace[1], Rotation::prev());
+            let t = meta.query_advice(trace[1], Rotation::cur());
+            let one = Expression::Constant(F::one());
+            vec![q_first * t.clone(), q * (t - t_prev - one)]
+        });
+
+        meta.create_gate("first access", |meta| {
+            let q = meta.query_selector(q_sorted_first);
+            let value = meta.query_advice(sorted[2], Rotation::cur());
+            let is_write = meta.query_advice(sorted[3], Rotation::cur());
+            let one = Expression::Constant(F::one());
+            vec![
+                q.clone() * is_write.clone() * (one.clone() - is_write.clone()),
+                // memory starts zeroed
+                q * (one - is_write) * value,
+            ]
+        });
+
+        meta.create_gate("read after write", |meta| {
+            let q = meta.query_selector(q_sorted);
+            let same = meta.query_advice(same, Rotation::cur());
+            let addr_prev = meta.query_advice(sorted[0], Rotation::prev());
+            let addr = meta.query_advice(sorted[0], Rotation::cur());
+            let value_prev = meta.query_advice(sorted[2], Rotation::prev());
+            let value = meta.query_advice(sorted[2], Rotation::cur());
+            let is_write = meta.query_advice(sorted[3], Rotation::cur());
+            let one = Expression::Constant(F::one());
+            let is_read = one.clone() - is_write.clone();
+            vec![
+                q.clone() * is_write.clone() * is_read.clone(),
+                q.clone() * same.clone() * (one.clone() - same.clone()),
+                q.clone() * same.clone() * (addr - addr_prev),
+                // a read repeats the previous value of its address, or 0 on a new address
+                q.clone() * same.clone() * is_read.clone() * (value.clone() - value_prev),
+                q * (one - same) * is_read * value,
+            ]
+        });
+
+        // `assign_with_sorted` range checks every `step`
+        meta.create_gate("memory order", |meta| {
+            let q = meta.query_selector(q_sorted);
+            let same = meta.query_advice(same, Rotation::cur());
+            let step = meta.query_advice(step, Rotation::cur());
+            let addr_prev = meta.query_advice(sorted[0], Rotation::prev());
+            let addr = meta.query_advice(sorted[0], Rotation::cur());
+            let t_prev = meta.query_advice(sorted[1], Rotation::prev());
+            let t = meta.query_advice(sorted[1], Rotation::cur());
+            let one = Expression::Constant(F::one());
+            let growth = same.clone() * (t - t_prev - one.clone()) + (one.clone() - same) * (addr - addr_prev - one);
+            vec![q * (step - growth)]
+        });
+
+        MemoryConfig {
+            trace,
+            sorted,
+            same,
+            step,
+            q_trace_first,
+            q_trace,
+            q_sorted_first,
+            q_sorted,
+            range,
+            shuffle,
+        }
+    }
+
+    /// Loads the limb table of the step range checks, once per circuit
+    pub fn load_range_table(&self, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.range.load_table(layouter)
+    }
+
+    /// Assigns the trace of `accesses` in execution order and its sorted copy, returns the
+    /// `(addr, t, value, is_write)` cells of each trace row
+    pub fn assign(
+        &self,
+        layouter: impl Layouter<F>,
+        accesses: &[Value<MemoryAccess<F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        let sorted = accesses
+            .iter()
+            .enumerate()
+            .map(|(t, access)| access.map(|access| (t, access)))
+            .collect::<Value<Vec<_>>>()
+            .map(|mut sorted| {
+                sorted.sort_by_key(|(t, access)| (access.addr, *t));
+                sorted
+            });
+        let sorted = (0..accesses.len())
+            .map(|i| sorted.as_ref().map(|sorted| sorted[i]))
+            .collect::<Vec<_>>();
+        self.assign_with_sorted(layouter, accesses, &sorted)
+    }
+
+    /// `assign` with the sorted copy given as `(t, access)` rows
+    fn assign_with_sorted(
+        &self,
+        mut layouter: impl Layouter<F>,
+        accesses: &[Value<MemoryAccess<F>>],
+        sorted: &[Value<(usize, MemoryAccess<F>)>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        let trace = layouter.assign_region(
+            || "memory trace",
+            |mut region| {
+                let mut rows = vec![];
+                for (t, access) in accesses.iter().enumerate() {
+                    if t == 0 {
+                        self.config.q_trace_first.enable(&mut region, t)?;
+                    } else {
+                        self.config.q_trace.enable(&mut region, t)?;
+                    }
+                    let row = self
+                        .config
+                        .trace
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| region.assign_advice(|| "trace", *col, t, || access.map(|a| a.row(t)[j])))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    rows.push(row);
+                }
+                Ok(rows)
+            },
+        )?;
+
+        let (sorted, steps) = layouter.assign_region(
+            || "memory sorted",
+            |mut region| {
+                let mut rows = vec![];
+                let mut steps = vec![];
+                for (i, entry) in sorted.iter().enumerate() {
+                    if i == 0 {
+                        self.config.q_sorted_first.enable(&mut region, i)?;
+                    } else {
+                        self.config.q_sorted.enable(&mut region, i)?;
+                        let same = entry
+                            .zip(sorted[i - 1])
+                            .map(|((_, a), (_, b))| F::from((a.addr == b.addr) as u64));
+                        region.assign_advice(|| "same", self.config.same, i, || same)?;
+                        // in the field, so an unsorted copy gets a step the range check rejects
+                        let step = entry.zip(sorted[i - 1]).map(|((t, a), (t_prev, b))| {
+                            if a.addr == b.addr {
+                                F::from(t as u64) - F::from(t_prev as u64) - F::one()
+                            } else {
+                                F::from(a.addr) - F::from(b.addr) - F::one()
+                            }
+                        });
+                        steps.push(region.assign_advice(|| "step", self.config.step, i, || step)?);
+                    }
+                    let row = self
+                        .config
+                        .sorted
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| region.assign_advice(|| "sorted", *col, i, || entry.map(|(t, a)| a.row(t)[j])))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    rows.push(row);
+                }
+                Ok((rows, steps))
+            },
+        )?;
+
+        for step in steps.iter() {
+            self.range
+                .copy_range_check(layouter.namespace(|| "memory step"), step, STEP_BITS)?;
+        }
+        self.shuffle
+            .copy_shuffle(layouter.namespace(|| "memory permutation"), &trace, &sorted)?;
+        Ok(trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr as Fp};
+
+    use super::*;
+
+    /// Assigns `accesses`, with `sorted` as the sorted copy when given
+    struct MemoryCircuit {
+        accesses: Vec<Value<MemoryAccess<Fp>>>,
+        sorted: Option<Vec<Value<(usize, MemoryAccess<Fp>)>>>,
+    }
+
+    impl Circuit<Fp> for MemoryCircuit {
+        type Config = MemoryConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            MemoryCircuit {
+                accesses: vec![Value::unknown(); self.accesses.len()],
+                sorted: self.sorted.as_ref().map(|sorted| vec![Value::unknown(); sorted.len()]),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            MemoryChip::configure(meta, 6)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = MemoryChip::construct(config);
+            chip.load_range_table(layouter.namespace(|| "range"))?;
+            match &self.sorted {
+                Some(sorted) => chip.assign_with_sorted(layouter.namespace(|| "memory"), &self.accesses, sorted)?,
+                None => chip.assign(layouter.namespace(|| "memory"), &self.accesses)?,
+            };
+            Ok(())
+        }
+    }
+
+    fn read(addr: u64, value: u64) -> MemoryAccess<Fp> {
+        MemoryAccess::read(addr, Fp::from(value))
+    }
+
+    fn write(addr: u64, value: u64) -> MemoryAccess<Fp> {
+        MemoryAccess::write(addr, Fp::from(value))
+    }
+
+    fn consistent(accesses: &[MemoryAccess<Fp>], sorted: Option<Vec<(usize, MemoryAccess<Fp>)>>) -> bool {
+        let circuit = MemoryCircuit {
+            accesses: accesses.iter().map(|a| Value::known(*a)).collect(),
+            sorted: sorted.map(|sorted| sorted.into_iter().map(Value::known).collect()),
+        };
+        MockProver::run(7, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    fn trace() -> Vec<MemoryAccess<Fp>> {
+        vec![
+            write(5, 10),
+            read(5, 10),
+            write(3, 7),
+            read(9, 0),
+            read(3, 7),
+            write(5, 11),
+            read(5, 11),
+        ]
+    }
+
+    #[test]
+    fn test_memory_consistency() {
+        assert!(consistent(&trace(), None));
+        assert!(consistent(&[], None));
+
+        // a stale read
+        let mut stale = trace();
+        stale[6] = read(5, 10);
+        assert!(!consistent(&stale, None));
+
+        // a read of memory nothing wrote to
+        let mut uninitialized = trace();
+        uninitialized[3] = read(9, 1);
+        assert!(!consistent(&uninitialized, None));
+    }
+
+    #[test]
+    fn test_memory_sparse_addresses() {
+        // steps far past the 2^6 rows of the limb table
+        let accesses = vec![
+            write(1000, 1),
+            write(0, 2),
+            read(1000, 1),
+            write(u64::MAX, 3),
+            read(0, 2),
+            read(u64::MAX, 3),
+        ];
+        assert!(consistent(&accesses, None));
+
+        let mut stale = accesses;
+        stale[2] = read(1000, 2);
+        assert!(!consistent(&stale, None));
+    }
+
+    #[test]
+    fn test_memory_sorted_copy() {
+        let mut stale = trace();
+        stale[6] = read(5, 10);
+        let mut honest = stale.iter().copied().enumerate().collect::<Vec<_>>();
+        honest.sort_by_key(|(t, access)| (access.addr, *t));
+        let position = |sorted: &[(usize, MemoryAccess<Fp>)], t: usize| sorted.iter().position(|(u, _)| *u == t).unwrap();
+
+        // sorted by address and timestamp, the stale read comes after the write of 11
+        assert!(!consistent(&stale, Some(honest.clone())));
+
+        // placing it right after the write of 10 breaks the timestamp order
+        let mut reordered = honest.clone();
+        let entry = reordered.remove(position(&reordered, 6));
+        reordered.insert(position(&reordered, 0) + 1, entry);
+        assert!(!consistent(&stale, Some(reordered)));
+
+        // a consistent read in its place is no permutation of the trace
+        let mut replaced = honest;
+        let at = position(&replaced, 6);
+        replaced[at].1 = read(5, 11);
+        assert!(!consistent(&stale, Some(replaced)));
+    }
+}
@@ -0,0 +1,216 @@
+//! KZG proving pipeline on bn256: params setup, key generation, SHPLONK/GWC proofs and verification
+
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, Error, ProvingKey, VerifyingKey},
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::{ProverGWC, ProverSHPLONK, VerifierGWC, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+    },
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer},
+};
+use rand_core::OsRng;
+
+/// The KZG multi-opening argument used to create and check a proof,
+/// a proof only verifies with the scheme it was created with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MultiOpen {
+    #[default]
+    Shplonk,
+    Gwc,
+}
+
+/// Fresh (insecure, locally generated) KZG params for circuits of size `2^k`
+pub fn setup(k: u32) -> ParamsKZG<Bn256> {
+    ParamsKZG::<Bn256>::setup(k, OsRng)
+}
+
+pub fn keygen<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    circuit: &C,
+) -> Result<ProvingKey<G1Affine>, Error> {
+    let vk = keygen_vk(params, circuit)?;
+    keygen_pk(params, vk, circuit)
+}
+
+/// `instances` holds the values of each instance column
+pub fn prove<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[&[Fr]],
+    scheme: MultiOpen,
+) -> Result<Vec<u8>, Error> {
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    match scheme {
+        MultiOpen::Shplonk => create_proof::<
+            KZGCommitmentScheme<Bn256>,
+            ProverSHPLONK<'_, Bn256>,
+            Challenge255<G1Affine>,
+            _,
+            Blake2bWrite<Vec<u8>, G1Affine, Challenge255<G1Affine>>,
+            _,
+        >(params, pk, &[circuit], &[instances], OsRng, &mut transcript)?,
+        MultiOpen::Gwc => create_proof::<
+            KZGCommitmentScheme<Bn256>,
+            ProverGWC<'_, Bn256>,
+            Challenge255<G1Affine>,
+            _,
+            Blake2bWrite<Vec<u8>, G1Affine, Challenge255<G1Affine>>,
+            _,
+        >(params, pk, &[circuit], &[instances], OsRng, &mut transcript)?,
+    }
+    Ok(transcript.finalize())
+}
+
+pub fn verify(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    proof: &[u8],
+    instances: &[&[Fr]],
+    scheme: MultiOpen,
+) -> Result<(), Error> {
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    let strategy = SingleStrategy::new(params);
+    match scheme {
+        MultiOpen::Shplonk => verify_proof::<
+            KZGCommitmentScheme<Bn256>,
+            VerifierSHPLONK<'_, Bn256>,
+            Challenge255<G1Affine>,
+            Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+            SingleStrategy<'_, Bn256>,
+        >(params.verifier_params(), vk, strategy, &[instances], &mut transcript),
+        MultiOpen::Gwc => verify_proof::<
+            KZGCommitmentScheme<Bn256>,
+            VerifierGWC<'_, Bn256>,
+            Challenge255<G1Affine>,
+            Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+            SingleStrategy<'_, Bn256>,
+        >(params.verifier_params(), vk, strategy, &[instances], &mut transcript),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use halo2_proofs::circuit::Value;
+
+    use super::*;
+    use crate::lookup_padding::{
+        AdviceLookup, FixedLookup, InstanceCopyLookup, InstanceLookup, LookupTable, MyCircuit, StrategyChoice,
+    };
+
+    fn witness(a: &[u64]) -> Vec<Value<Fr>> {
+        a.iter().map(|v| Value::known(Fr::from(*v))).collect()
+    }
+
+    /// Runs keygen, proving and verification with a fresh params set
+    fn prove_and_verify<C: Circuit<Fr>>(k: u32, circuit: C, scheme: MultiOpen) -> Result<(), Error> {
+        let params = setup(k);
+        let pk = keygen(&params, &circuit.without_witnesses())?;
+        let proof = prove(&params, &pk, circuit, &[], scheme)?;
+        verify(&params, pk.get_vk(), &proof, &[], scheme)
+    }
+
+    #[test]
+    fn test_valid_witness_verifies() {
+        let k = 5;
+        for scheme in [MultiOpen::Shplonk, MultiOpen::Gwc] {
+            let circuit = MyCircuit::<Fr>::new(witness(&[1, 2, 3, 9]));
+            prove_and_verify(k, circuit, scheme).unwrap();
+
+            let circuit = MyCircuit::<Fr, AdviceLookup<true>>::new(witness(&[1, 2, 3, 9]));
+            prove_and_verify(k, circuit, scheme).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lookup_failure_fails_to_prove() {
+        let k = 5;
+        let params = setup(k);
+        for scheme in [MultiOpen::Shplonk, MultiOpen::Gwc] {
+            // 10 is in no row of either table, padded or not
+            let circuit = MyCircuit::<Fr>::new(witness(&[1, 10]));
+            let pk = keygen(&params, &circuit.without_witnesses()).unwrap();
+            assert_matches!(prove(&params, &pk, circuit, &[], scheme), Err(Error::ConstraintSystemFailure));
+
+            // 0 is only in the zero padding, which the hardened table excludes
+            let circuit = MyCircuit::<Fr, AdviceLookup<true>>::new(witness(&[0, 1]));
+            let pk = keygen(&params, &circuit.without_witnesses()).unwrap();
+            assert_matches!(prove(&params, &pk, circuit, &[], scheme), Err(Error::ConstraintSystemFailure));
+        }
+    }
+
+    #[test]
+    fn test_verifier_rejects_proof_for_other_table() {
+        let k = 5;
+        let params = setup(k);
+        let table = LookupTable::default();
+        // `{10, 2..9}`: the same rows, so the table values are all that differs from `{1..9}`
+        let other = LookupTable::from_values((1..10u64).map(|i| Fr::from(if i == 1 { 10 } else { i })));
+
+        for scheme in [MultiOpen::Shplonk, MultiOpen::Gwc] {
+            // the fixed table is part of the vk, so a proof of 10 made with keys for a table
+            // holding 10 passes the prover and only the verifier with the real vk rejects it
+            let circuit = MyCircuit::<Fr, FixedLookup<true>>::with_table(witness(&[1, 10]), other.clone());
+            let other_pk = keygen(&params, &circuit.without_witnesses()).unwrap();
+            let proof = prove(&params, &other_pk, circuit, &[], scheme).unwrap();
+            verify(&params, other_pk.get_vk(), &proof, &[], scheme).unwrap();
+
+            let circuit = MyCircuit::<Fr, FixedLookup<true>>::with_table(vec![Value::unknown(); 2], table.clone());
+            let pk = keygen(&params, &circuit).unwrap();
+            assert!(verify(&params, pk.get_vk(), &proof, &[], scheme).is_err());
+        }
+    }
+
+    #[test]
+    fn test_padding_hole_reaches_real_verifier() {
+        // the plain advice table accepts 0 in a real proof too, not only in MockProver
+        let circuit = MyCircuit::<Fr>::new(witness(&[0, 1, 2, 3]));
+        prove_and_verify(5, circuit, MultiOpen::Shplonk).unwrap();
+    }
+
+    #[test]
+    fn test_proof_checked_with_its_own_scheme() {
+        let k = 5;
+        let circuit = MyCircuit::<Fr>::new(witness(&[1, 2, 3]));
+        let params = setup(k);
+        let pk = keygen(&params, &circuit.without_witnesses()).unwrap();
+        let proof = prove(&params, &pk, circuit, &[], MultiOpen::Shplonk).unwrap();
+
+        verify(&params, pk.get_vk(), &proof, &[], MultiOpen::Shplonk).unwrap();
+        assert!(verify(&params, pk.get_vk(), &proof, &[], MultiOpen::Gwc).is_err());
+
+        let mut tampered = proof.clone();
+        tampered[0] ^= 1;
+        assert!(verify(&params, pk.get_vk(), &tampered, &[], MultiOpen::Shplonk).is_err());
+    }
+
+    /// Proves `a` against the public table of the circuit, then verifies against `instance`
+    fn public_table_verifies<S: StrategyChoice>(a: &[u64], instance: &[Fr]) -> Result<(), Error> {
+        let k = 5;
+        let circuit = MyCircuit::<Fr, S>::new(witness(a));
+        let public = circuit.instances();
+        let params = setup(k);
+        let pk = keygen(&params, &circuit.without_witnesses())?;
+        let proof = prove(&params, &pk, circuit, &[&public[0]], MultiOpen::Shplonk)?;
+        verify(&params, pk.get_vk(), &proof, &[instance], MultiOpen::Shplonk)
+    }
+
+    #[test]
+    fn test_public_table_binds_proof() {
+        let table = MyCircuit::<Fr, InstanceLookup>::new(vec![]).instances().remove(0);
+        let mut other = table.clone();
+        other[9] = Fr::from(10);
+
+        public_table_verifies::<InstanceLookup>(&[1, 9], &table).unwrap();
+        public_table_verifies::<InstanceCopyLookup<true>>(&[1, 9], &table).unwrap();
+        // a proof made with one public table does not verify against another
+        assert!(public_table_verifies::<InstanceLookup>(&[1, 9], &other).is_err());
+        assert!(public_table_verifies::<InstanceCopyLookup<true>>(&[1, 9], &other).is_err());
+    }
+}
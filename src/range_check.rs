@@ -0,0 +1,281 @@
+//! Range checks on top of a `TableColumn` lookup, the same path `LookupChip` uses for `t1`.
+//!
+//! The table holds `0..2^b`. A value `v` of `n` bits is decomposed into `m = ceil(n / b)`
+//! limbs with a running sum in a single advice column:
+//!
+//! ```text
+//! z_0 = v,  z_{i+1} = (z_i - a_i) / 2^b,  z_m = 0
+//! ```
+//!
+//! Every limb `a_i = z_i - 2^b * z_{i+1}` is looked up in the table. When `b` does not divide
+//! `n`, the last limb only has `n mod b` bits, so it is also looked up shifted by
+//! `2^(b - n mod b)`, which only stays in the table for short enough limbs.
+
+use std::marker::PhantomData;
+
+use ff::{Field, PrimeField};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+#[derive(Clone, Debug)]
+pub struct RangeCheckConfig {
+    z: Column<Advice>,
+    // limb lookup on every row but the last of a running sum
+    q_lookup: Selector,
+    // shifted lookup on the row of a short final limb
+    q_short: Selector,
+    // `z_m = 0` on the last row
+    q_end: Selector,
+    // `2^(b - n mod b)` on the rows with `q_short` enabled
+    shift: Column<Fixed>,
+    table: TableColumn,
+    limb_bits: usize,
+}
+
+pub struct RangeCheckChip<F: FieldExt> {
+    config: RangeCheckConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> RangeCheckChip<F> {
+    pub fn construct(config: RangeCheckConfig) -> Self {
+        RangeCheckChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, limb_bits: usize) -> RangeCheckConfig {
+        assert!(limb_bits > 0 && limb_bits <= 24, "limb width must be in 1..=24 bits");
+
+        let z = meta.advice_column();
+        let q_lookup = meta.complex_selector();
+        let q_short = meta.complex_selector();
+        let q_end = meta.selector();
+        let shift = meta.fixed_column();
+        let table = meta.lookup_table_column();
+
+        meta.enable_equality(z);
+
+        let two_pow_b = Expression::Constant(F::from(1 << limb_bits));
+
+        // 0 is in the table, so rows without a limb can look up `q * limb = 0`
+        meta.lookup("range limb", |meta| {
+            let q = meta.query_selector(q_lookup);
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+            vec![(q * (z_cur - z_next * two_pow_b.clone()), table)]
+        });
+
+        meta.lookup("range short limb", |meta| {
+            let q = meta.query_selector(q_short);
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+            let shift = meta.query_fixed(shift, Rotation::cur());
+            vec![(q * (z_cur - z_next * two_pow_b.clone()) * shift, table)]
+        });
+
+        meta.create_gate("running sum end", |meta| {
+            let q = meta.query_selector(q_end);
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            vec![q * z_cur]
+        });
+
+        RangeCheckConfig {
+            z,
+            q_lookup,
+            q_short,
+            q_end,
+            shift,
+            table,
+            limb_bits,
+        }
+    }
+
+    /// Loads `0..2^b` into the table, once per circuit
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "range table",
+            |mut table| {
+                for i in 0..1usize << self.config.limb_bits {
+                    table.assign_cell(
+                        || "range table",
+                        self.config.table,
+                        i,
+                        || Value::known(F::from(i as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Witnesses `value` and checks it fits in `num_bits` bits, returns the witnessed cell
+    pub fn witness_range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Value<F>,
+        num_bits: usize,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "range check",
+            |mut region| {
+                let z_0 = region.assign_advice(|| "z_0", self.config.z, 0, || value)?;
+                self.running_sum(&mut region, &z_0, num_bits)?;
+                Ok(z_0)
+            },
+        )
+    }
+
+    /// Checks that the value of `cell`, assigned by another chip, fits in `num_bits` bits
+    pub fn copy_range_check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        num_bits: usize,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "range check",
+            |mut region| {
+                let z_0 = cell.copy_advice(|| "z_0", &mut region, self.config.z, 0)?;
+                self.running_sum(&mut region, &z_0, num_bits)
+            },
+        )
+    }
+
+    /// Assigns `z_1..z_m` below `z_0` at offset 0 and enables the limb checks
+    fn running_sum(
+        &self,
+        region: &mut Region<'_, F>,
+        z_0: &AssignedCell<F, F>,
+        num_bits: usize,
+    ) -> Result<(), Error> {
+        assert!(
+            num_bits > 0 && num_bits <= F::CAPACITY as usize,
+            "the running sum must not wrap around the field modulus"
+        );
+        let limb_bits = self.config.limb_bits;
+        let num_limbs = (num_bits + limb_bits - 1) / limb_bits;
+        let short_bits = num_bits % limb_bits;
+        let two_pow_b_inv = Value::known(F::from(1 << limb_bits).invert().unwrap());
+
+        let limbs = z_0.value().map(|v| decompose(v, limb_bits, num_limbs));
+        let mut z = z_0.value().copied();
+        for i in 0..num_limbs {
+            self.config.q_lookup.enable(region, i)?;
+            let limb = limbs.as_ref().map(|limbs| F::from(limbs[i]));
+            z = (z - limb) * two_pow_b_inv;
+            region.assign_advice(|| format!("z_{}", i + 1), self.config.z, i + 1, || z)?;
+        }
+
+        if short_bits != 0 {
+            let row = num_limbs - 1;
+            self.config.q_short.enable(region, row)?;
+            region.assign_fixed(
+                || "short limb shift",
+                self.config.shift,
+                row,
+                || Value::known(F::from(1 << (limb_bits - short_bits))),
+            )?;
+        }
+
+        self.config.q_end.enable(region, num_limbs)
+    }
+}
+
+/// The `num_limbs` lowest `limb_bits`-bit limbs of `value`, least significant first
+pub fn decompose<F: FieldExt>(value: &F, limb_bits: usize, num_limbs: usize) -> Vec<u64> {
+    let repr = value.to_repr();
+    let bytes = repr.as_ref();
+    let bit = |i: usize| bytes.get(i / 8).map_or(0, |byte| (byte >> (i % 8)) & 1) as u64;
+    (0..num_limbs)
+        .map(|limb| (0..limb_bits).map(|j| bit(limb * limb_bits + j) << j).sum())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr as Fp,
+    };
+
+    use super::*;
+
+    /// Range checks each `(value, num_bits)`, then copies the checked cell into a second check
+    struct RangeCheckCircuit<const LIMB_BITS: usize> {
+        values: Vec<(Value<Fp>, usize)>,
+    }
+
+    impl<const LIMB_BITS: usize> Circuit<Fp> for RangeCheckCircuit<LIMB_BITS> {
+        type Config = RangeCheckConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            RangeCheckCircuit {
+                values: self.values.iter().map(|(_, bits)| (Value::unknown(), *bits)).collect(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            RangeCheckChip::configure(meta, LIMB_BITS)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = RangeCheckChip::<Fp>::construct(config);
+            chip.load_table(layouter.namespace(|| "table"))?;
+            for (value, num_bits) in self.values.iter() {
+                let cell = chip.witness_range_check(layouter.namespace(|| "witness"), *value, *num_bits)?;
+                chip.copy_range_check(layouter.namespace(|| "copy"), &cell, *num_bits)?;
+            }
+            Ok(())
+        }
+    }
+
+    fn check<const LIMB_BITS: usize>(k: u32, value: Fp, num_bits: usize) -> bool {
+        let circuit = RangeCheckCircuit::<LIMB_BITS> {
+            values: vec![(Value::known(value), num_bits)],
+        };
+        MockProver::run(k, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    fn pow2(bits: u64) -> Fp {
+        Fp::from(2).pow_vartime([bits])
+    }
+
+    #[test]
+    fn test_range_check_wide_values() {
+        let k = 9;
+        assert!(check::<8>(k, Fp::from(u64::MAX), 64));
+        assert!(!check::<8>(k, pow2(64), 64));
+
+        assert!(check::<8>(k, Fp::from_u128(u128::MAX), 128));
+        assert!(!check::<8>(k, pow2(128), 128));
+
+        // 253 = 31 * 8 + 5, the top limb is short
+        assert!(check::<8>(k, pow2(253) - Fp::one(), 253));
+        assert!(!check::<8>(k, pow2(253), 253));
+        assert!(!check::<8>(k, -Fp::one(), 253));
+    }
+
+    #[test]
+    fn test_short_final_limb() {
+        let k = 6;
+        // 10 = 4 + 4 + 2 bits
+        assert!(check::<4>(k, Fp::from(1023), 10));
+        // the final limb is 4, which fits in 4 bits but not in 2
+        assert!(!check::<4>(k, Fp::from(1024), 10));
+        assert!(!check::<4>(k, Fp::from(1024 + 512), 10));
+
+        // no short limb when the width divides the bit count
+        assert!(check::<4>(k, Fp::from(4095), 12));
+        assert!(!check::<4>(k, Fp::from(4096), 12));
+
+        // a single short limb
+        assert!(check::<4>(k, Fp::from(7), 3));
+        assert!(!check::<4>(k, Fp::from(8), 3));
+    }
+}
@@ -0,0 +1,315 @@
+//! `MyCircuit` scenarios read from JSON, so new lookups can be tried without recompiling:
+//!
+//! ```json
+//! {
+//!     "witness": [0, 1, "2", "0x2a"],
+//!     "table": [1, 2, 3, 42],
+//!     "strategy": "advice",
+//!     "hardened": true,
+//!     "k": 6
+//! }
+//! ```
+//!
+//! Field elements are JSON integers or strings holding a decimal or `0x` hex number, strings
+//! for values above `u64`. A value that is not below the modulus of the scalar field is
+//! rejected rather than reduced. Only `witness` is required: `table` defaults to `{1..9}`,
+//! `strategy` to `advice` (the names of `--strategy`), `hardened` to false and `k` to the
+//! smallest one the circuit fits in.
+
+use std::{fmt, fs, io, path::Path};
+
+use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
+use serde::Deserialize;
+
+use crate::lookup_padding::{LookupStrategy, LookupTable, MyCircuit, RegionOverflow, StrategyChoice};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScenario {
+    witness: Vec<RawElement>,
+    table: Option<Vec<RawElement>>,
+    strategy: Option<String>,
+    #[serde(default)]
+    hardened: bool,
+    k: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawElement {
+    Number(u64),
+    String(String),
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    /// `value` at `field` is not a decimal or `0x` hex number
+    InvalidElement { field: String, value: String },
+    /// `value` at `field` is not below the modulus of the scalar field
+    NonCanonical { field: String, value: String },
+    EmptyTable,
+    UnknownStrategy(String),
+    /// `Scenario::circuit` was called with a strategy other than the one of the scenario
+    StrategyMismatch {
+        scenario: (LookupStrategy, bool),
+        circuit: (LookupStrategy, bool),
+    },
+    Overflow(RegionOverflow),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "{}", e),
+            ScenarioError::Json(e) => write!(f, "invalid scenario: {}", e),
+            ScenarioError::InvalidElement { field, value } => {
+                write!(f, "{}: {:?} is not a decimal or 0x hex number", field, value)
+            }
+            ScenarioError::NonCanonical { field, value } => {
+                write!(f, "{}: {} is not a canonical field element, it is not below the modulus", field, value)
+            }
+            ScenarioError::EmptyTable => write!(f, "table: a lookup table needs at least one row"),
+            ScenarioError::UnknownStrategy(e) => write!(f, "strategy: {}", e),
+            ScenarioError::StrategyMismatch { scenario, circuit } => write!(
+                f,
+                "strategy: the scenario is (strategy, hardened) = {:?}, the circuit {:?}",
+                scenario, circuit
+            ),
+            ScenarioError::Overflow(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(e: io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError {
+    fn from(e: serde_json::Error) -> Self {
+        ScenarioError::Json(e)
+    }
+}
+
+impl From<RegionOverflow> for ScenarioError {
+    fn from(e: RegionOverflow) -> Self {
+        ScenarioError::Overflow(e)
+    }
+}
+
+/// Parses a decimal or `0x` hex number, fails with `NonCanonical` when it is not below the
+/// modulus of `Fr`
+fn parse_element(value: &str, field: &str) -> Result<Fr, ScenarioError> {
+    let invalid = || ScenarioError::InvalidElement {
+        field: field.to_string(),
+        value: value.to_string(),
+    };
+    let (digits, radix) = match value.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (value, 10),
+    };
+    if digits.is_empty() {
+        return Err(invalid());
+    }
+
+    // little endian, like `Fr::from_bytes`
+    let mut bytes = [0u8; 32];
+    let mut fits = true;
+    for c in digits.chars() {
+        let mut carry = c.to_digit(radix).ok_or_else(invalid)?;
+        for byte in bytes.iter_mut() {
+            let v = *byte as u32 * radix + carry;
+            *byte = v as u8;
+            carry = v >> 8;
+        }
+        fits &= carry == 0;
+    }
+
+    match Option::<Fr>::from(Fr::from_bytes(&bytes)) {
+        Some(element) if fits => Ok(element),
+        _ => Err(ScenarioError::NonCanonical {
+            field: field.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn parse_elements(elements: &[RawElement], name: &str) -> Result<Vec<Fr>, ScenarioError> {
+    elements
+        .iter()
+        .enumerate()
+        .map(|(i, element)| match element {
+            RawElement::Number(v) => Ok(Fr::from(*v)),
+            RawElement::String(v) => parse_element(v, &format!("{}[{}]", name, i)),
+        })
+        .collect()
+}
+
+/// The inputs of one `MyCircuit` run
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub witness: Vec<Fr>,
+    pub table: LookupTable<Fr>,
+    pub strategy: LookupStrategy,
+    pub hardened: bool,
+    /// The smallest `k` that fits when not set
+    pub k: Option<u32>,
+}
+
+impl Default for Scenario {
+    /// The original demo: `[0, 1, 2, 3]` looked up in `{1..9}` with a plain advice table
+    fn default() -> Self {
+        Scenario {
+            witness: [0u64, 1, 2, 3].map(Fr::from).to_vec(),
+            table: LookupTable::default(),
+            strategy: LookupStrategy::Advice,
+            hardened: false,
+            k: None,
+        }
+    }
+}
+
+impl Scenario {
+    pub fn from_json(json: &str) -> Result<Self, ScenarioError> {
+        let raw: RawScenario = serde_json::from_str(json)?;
+        let table = match raw.table {
+            Some(table) if table.is_empty() => return Err(ScenarioError::EmptyTable),
+            Some(table) => LookupTable::from_values(parse_elements(&table, "table")?),
+            None => LookupTable::default(),
+        };
+        let strategy = match raw.strategy {
+            Some(name) => name.parse().map_err(ScenarioError::UnknownStrategy)?,
+            None => LookupStrategy::Advice,
+        };
+
+        Ok(Scenario {
+            witness: parse_elements(&raw.witness, "witness")?,
+            table,
+            strategy,
+            hardened: raw.hardened,
+            k: raw.k,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ScenarioError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// The circuit and its `k`: `k` when set and the circuit fits in it, the smallest one
+    /// otherwise. Fails with `StrategyMismatch` when `S` is not the strategy of the scenario.
+    pub fn circuit<S: StrategyChoice>(&self) -> Result<(MyCircuit<Fr, S>, u32), ScenarioError> {
+        if (S::STRATEGY, S::HARDENED) != (self.strategy, self.hardened) {
+            return Err(ScenarioError::StrategyMismatch {
+                scenario: (self.strategy, self.hardened),
+                circuit: (S::STRATEGY, S::HARDENED),
+            });
+        }
+        let (circuit, min_k) = MyCircuit::builder()
+            .inputs(self.witness.iter().map(|v| Value::known(*v)).collect())
+            .table(self.table.clone())
+            .build()?;
+        match self.k {
+            Some(k) => {
+                circuit.check_size(k)?;
+                Ok((circuit, k))
+            }
+            None => Ok((circuit, min_k)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use halo2_proofs::dev::MockProver;
+
+    use super::*;
+    use crate::lookup_padding::{AdviceLookup, FixedLookup};
+
+    /// `p - 1`, the largest canonical element
+    const P_MINUS_ONE: &str = "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+
+    fn verifies<S: StrategyChoice>(scenario: &Scenario) -> bool {
+        let (circuit, k) = scenario.circuit::<S>().unwrap();
+        MockProver::run(k, &circuit, circuit.instances()).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_scenario_from_json() {
+        let scenario = Scenario::from_json(
+            r#"{ "witness": [0, "1", "0x2a"], "table": [1, 42, "0x0"], "strategy": "fixed", "hardened": true }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.witness, [0u64, 1, 42].map(Fr::from).to_vec());
+        assert_eq!(scenario.table.len(), 3);
+        assert_eq!((scenario.strategy, scenario.hardened, scenario.k), (LookupStrategy::Fixed, true, None));
+        assert!(verifies::<FixedLookup<true>>(&scenario));
+
+        // the default table: 0 only passes through the padding of the plain advice table
+        let scenario = Scenario::from_json(r#"{ "witness": [0, 1, 2, 3], "k": 6 }"#).unwrap();
+        assert_eq!(scenario.k, Some(6));
+        assert!(verifies::<AdviceLookup>(&scenario));
+        let hardened = Scenario {
+            hardened: true,
+            ..scenario
+        };
+        assert!(!verifies::<AdviceLookup<true>>(&hardened));
+    }
+
+    #[test]
+    fn test_scenario_field_elements() {
+        let witness = |value: &str| Scenario::from_json(&format!(r#"{{ "witness": ["{}"] }}"#, value));
+
+        assert_eq!(witness(P_MINUS_ONE).unwrap().witness, vec![-Fr::one()]);
+        assert_eq!(witness("0x00ff").unwrap().witness, vec![Fr::from(255)]);
+
+        // p, and numbers past 2^256, would wrap around to other elements
+        let p = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        assert_matches!(witness(p), Err(ScenarioError::NonCanonical { field, .. }) if field == "witness[0]");
+        assert_matches!(witness(&format!("{}0", p)), Err(ScenarioError::NonCanonical { .. }));
+        assert_matches!(witness(&format!("0x1{}", "0".repeat(64))), Err(ScenarioError::NonCanonical { .. }));
+
+        for invalid in ["", "0x", "-1", "1.5", "0xg"] {
+            assert_matches!(witness(invalid), Err(ScenarioError::InvalidElement { .. }));
+        }
+        assert_matches!(
+            Scenario::from_json(r#"{ "witness": [1], "table": [1, "x"] }"#),
+            Err(ScenarioError::InvalidElement { field, .. }) if field == "table[1]"
+        );
+    }
+
+    #[test]
+    fn test_invalid_scenarios() {
+        assert_matches!(Scenario::from_json(r#"{ "table": [1] }"#), Err(ScenarioError::Json(_)));
+        assert_matches!(Scenario::from_json(r#"{ "witness": [1], "tabel": [1] }"#), Err(ScenarioError::Json(_)));
+        assert_matches!(Scenario::from_json(r#"{ "witness": [1], "table": [] }"#), Err(ScenarioError::EmptyTable));
+        assert_matches!(
+            Scenario::from_json(r#"{ "witness": [1], "strategy": "lookup" }"#),
+            Err(ScenarioError::UnknownStrategy(_))
+        );
+
+        // 40 table rows do not fit in k = 5
+        let table = (1..=40).map(|v| v.to_string()).collect::<Vec<_>>().join(", ");
+        let scenario = Scenario::from_json(&format!(r#"{{ "witness": [1], "table": [{}], "k": 5 }}"#, table)).unwrap();
+        assert_matches!(
+            scenario.circuit::<AdviceLookup>().map(|_| ()),
+            Err(ScenarioError::Overflow(overflow)) if overflow.region == "t2"
+        );
+
+        // a fixed table scenario run with the advice circuit
+        let scenario = Scenario::from_json(r#"{ "witness": [1], "strategy": "fixed" }"#).unwrap();
+        assert_matches!(
+            scenario.circuit::<AdviceLookup>().map(|_| ()),
+            Err(ScenarioError::StrategyMismatch { scenario, .. }) if scenario == (LookupStrategy::Fixed, false)
+        );
+        assert_matches!(
+            scenario.circuit::<FixedLookup<true>>().map(|_| ()),
+            Err(ScenarioError::StrategyMismatch { .. })
+        );
+    }
+}
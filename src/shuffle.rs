@@ -0,0 +1,336 @@
+//! Multiset equality of two lists of tuples, next to the set membership of `LookupChip`.
+//!
+//! A lookup only asks that every input tuple is *some* table row, so duplicates and missing
+//! rows go unnoticed. `ShuffleChip` checks that `shuffled` is a permutation of `input`, each
+//! row used exactly once, with a grand product over a second-phase column `z`:
+//!
+//! ```text
+//! z_0 = 1,  z_{i+1} = z_i · (A_i + γ) / (B_i + γ),  z_n = 1
+//! ```
+//!
+//! where `A_i`/`B_i` compress the tuples of row `i` with a challenge `θ` and `γ` is a second
+//! challenge, both drawn after the first phase. The scroll `halo2_proofs` pinned in
+//! Cargo.lock (branch `develop`, commit `92fe9b3e`) has no native shuffle argument, the grand
+//! product only needs the phases and challenges the compressed lookups already use.
+
+use std::marker::PhantomData;
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Chip, Layouter, Region, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+use crate::compressed::compress;
+
+#[derive(Clone, Debug)]
+pub struct ShuffleConfig {
+    input: Vec<Column<Advice>>,
+    shuffled: Vec<Column<Advice>>,
+    // the grand product, one row longer than the shuffled lists
+    z: Column<Advice>,
+    // `z = 1` on the first and on the last row
+    q_first: Selector,
+    q_last: Selector,
+    // `z_{i+1} · (B_i + γ) = z_i · (A_i + γ)` on every row of the lists
+    q_shuffle: Selector,
+    theta: Challenge,
+    gamma: Challenge,
+}
+
+impl ShuffleConfig {
+    /// Number of columns in each shuffled tuple
+    pub fn width(&self) -> usize {
+        self.input.len()
+    }
+}
+
+/// Shuffles of `input` tuples, the cells of both sides are returned so callers can keep
+/// constraining them
+pub trait ShuffleInstructions<F: FieldExt>: Chip<F> {
+    /// Witnesses `input` and `shuffled` and checks they are the same multiset of tuples
+    #[allow(clippy::type_complexity)]
+    fn witness_shuffle(
+        &self,
+        layouter: impl Layouter<F>,
+        input: &[Vec<Value<F>>],
+        shuffled: &[Vec<Value<F>>],
+    ) -> Result<(Vec<Vec<AssignedCell<F, F>>>, Vec<Vec<AssignedCell<F, F>>>), Error>;
+
+    /// Copies cells assigned by other chips and checks they are the same multiset of tuples
+    #[allow(clippy::type_complexity)]
+    fn copy_shuffle(
+        &self,
+        layouter: impl Layouter<F>,
+        input: &[Vec<AssignedCell<F, F>>],
+        shuffled: &[Vec<AssignedCell<F, F>>],
+    ) -> Result<(Vec<Vec<AssignedCell<F, F>>>, Vec<Vec<AssignedCell<F, F>>>), Error>;
+}
+
+pub struct ShuffleChip<F: FieldExt> {
+    config: ShuffleConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> Chip<F> for ShuffleChip<F> {
+    type Config = ShuffleConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt> ShuffleInstructions<F> for ShuffleChip<F> {
+    fn witness_shuffle(
+        &self,
+        layouter: impl Layouter<F>,
+        input: &[Vec<Value<F>>],
+        shuffled: &[Vec<Value<F>>],
+    ) -> Result<(Vec<Vec<AssignedCell<F, F>>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        self.check_shape(input, shuffled)?;
+        self.assign_shuffle(
+            layouter,
+            input.len(),
+            |region, i, j, col| region.assign_advice(|| "input", col, i, || input[i][j]),
+            |region, i, j, col| region.assign_advice(|| "shuffled", col, i, || shuffled[i][j]),
+        )
+    }
+
+    fn copy_shuffle(
+        &self,
+        layouter: impl Layouter<F>,
+        input: &[Vec<AssignedCell<F, F>>],
+        shuffled: &[Vec<AssignedCell<F, F>>],
+    ) -> Result<(Vec<Vec<AssignedCell<F, F>>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        self.check_shape(input, shuffled)?;
+        self.assign_shuffle(
+            layouter,
+            input.len(),
+            |region, i, j, col| input[i][j].copy_advice(|| "input", region, col, i),
+            |region, i, j, col| shuffled[i][j].copy_advice(|| "shuffled", region, col, i),
+        )
+    }
+}
+
+impl<F: FieldExt> ShuffleChip<F> {
+    pub fn construct(config: ShuffleConfig) -> Self {
+        ShuffleChip {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// `width` columns on each side of the shuffle
+    pub fn configure(meta: &mut ConstraintSystem<F>, width: usize) -> ShuffleConfig {
+        assert!(width > 0, "a shuffle needs at least one column");
+
+        let input = (0..width).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let shuffled = (0..width).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let theta = meta.challenge_usable_after(FirstPhase);
+        let gamma = meta.challenge_usable_after(FirstPhase);
+        let z = meta.advice_column_in(SecondPhase);
+        let q_first = meta.selector();
+        let q_last = meta.selector();
+        let q_shuffle = meta.selector();
+
+        for column in input.iter().chain(shuffled.iter()) {
+            meta.enable_equality(*column);
+        }
+
+        meta.create_gate("shuffle ends", |meta| {
+            let q_first = meta.query_selector(q_first);
+            let q_last = meta.query_selector(q_last);
+            let z = meta.query_advice(z, Rotation::cur());
+            let one = Expression::Constant(F::one());
+            vec![q_first * (z.clone() - one.clone()), q_last * (z - one)]
+        });
+
+        meta.create_gate("shuffle product", |meta| {
+            let q = meta.query_selector(q_shuffle);
+            let theta = meta.query_challenge(theta);
+            let gamma = meta.query_challenge(gamma);
+            let input = input.iter().map(|c| meta.query_advice(*c, Rotation::cur())).collect::<Vec<_>>();
+            let shuffled = shuffled.iter().map(|c| meta.query_advice(*c, Rotation::cur())).collect::<Vec<_>>();
+            let z_cur = meta.query_advice(z, Rotation::cur());
+            let z_next = meta.query_advice(z, Rotation::next());
+            vec![
+                q * (z_next * (compress(&shuffled, &theta) + gamma.clone())
+                    - z_cur * (compress(&input, &theta) + gamma)),
+            ]
+        });
+
+        ShuffleConfig {
+            input,
+            shuffled,
+            z,
+            q_first,
+            q_last,
+            q_shuffle,
+            theta,
+            gamma,
+        }
+    }
+
+    /// Both sides have the same number of tuples, each `width` wide
+    fn check_shape<T>(&self, input: &[Vec<T>], shuffled: &[Vec<T>]) -> Result<(), Error> {
+        let width = self.config.width();
+        if input.len() != shuffled.len() || input.iter().chain(shuffled.iter()).any(|tuple| tuple.len() != width) {
+            return Err(Error::Synthesis);
+        }
+        Ok(())
+    }
+
+    /// Assigns `num_rows` rows of both sides with `assign_input`/`assign_shuffled(region, row,
+    /// column index, column)` and the grand product of their compressions below them
+    #[allow(clippy::type_complexity)]
+    fn assign_shuffle(
+        &self,
+        mut layouter: impl Layouter<F>,
+        num_rows: usize,
+        mut assign_input: impl FnMut(&mut Region<'_, F>, usize, usize, Column<Advice>) -> Result<AssignedCell<F, F>, Error>,
+        mut assign_shuffled: impl FnMut(&mut Region<'_, F>, usize, usize, Column<Advice>) -> Result<AssignedCell<F, F>, Error>,
+    ) -> Result<(Vec<Vec<AssignedCell<F, F>>>, Vec<Vec<AssignedCell<F, F>>>), Error> {
+        // unknown in the first phase, `z` is only assigned in the second
+        let theta = layouter.get_challenge(self.config.theta);
+        let gamma = layouter.get_challenge(self.config.gamma);
+        layouter.assign_region(
+            || "shuffle",
+            |mut region| {
+                self.config.q_first.enable(&mut region, 0)?;
+                let mut z = Value::known(F::one());
+                region.assign_advice(|| "z", self.config.z, 0, || z)?;
+
+                let (mut inputs, mut shuffled) = (vec![], vec![]);
+                for i in 0..num_rows {
+                    self.config.q_shuffle.enable(&mut region, i)?;
+                    let a = self
+                        .config
+                        .input
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| assign_input(&mut region, i, j, *col))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let b = self
+                        .config
+                        .shuffled
+                        .iter()
+                        .enumerate()
+                        .map(|(j, col)| assign_shuffled(&mut region, i, j, *col))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    let values = |cells: &[AssignedCell<F, F>]| {
+                        cells.iter().map(|cell| cell.value().copied()).collect::<Vec<_>>()
+                    };
+                    let numerator = compress(&values(&a), &theta) + gamma;
+                    let denominator = compress(&values(&b), &theta) + gamma;
+                    // a zero denominator has negligible probability, the gate then fails
+                    z = z * numerator * denominator.map(|d| d.invert().unwrap_or(F::zero()));
+                    region.assign_advice(|| "z", self.config.z, i + 1, || z)?;
+
+                    inputs.push(a);
+                    shuffled.push(b);
+                }
+
+                self.config.q_last.enable(&mut region, num_rows)?;
+                Ok((inputs, shuffled))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr as Fp};
+
+    use super::*;
+    use crate::lookup_padding::{AdviceLookup, LookupTable, MyCircuit};
+
+    /// Checks that `shuffled` is a permutation of `input`
+    struct ShuffleCircuit<const W: usize> {
+        input: Vec<Vec<Value<Fp>>>,
+        shuffled: Vec<Vec<Value<Fp>>>,
+    }
+
+    impl<const W: usize> Circuit<Fp> for ShuffleCircuit<W> {
+        type Config = ShuffleConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            ShuffleCircuit {
+                input: vec![vec![Value::unknown(); W]; self.input.len()],
+                shuffled: vec![vec![Value::unknown(); W]; self.shuffled.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            ShuffleChip::configure(meta, W)
+        }
+
+        fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            ShuffleChip::<Fp>::construct(config).witness_shuffle(layouter, &self.input, &self.shuffled)?;
+            Ok(())
+        }
+    }
+
+    fn tuples<const W: usize>(rows: &[[u64; W]]) -> Vec<Vec<Value<Fp>>> {
+        rows.iter()
+            .map(|row| row.iter().map(|v| Value::known(Fp::from(*v))).collect())
+            .collect()
+    }
+
+    fn shuffles<const W: usize>(input: &[[u64; W]], shuffled: &[[u64; W]]) -> bool {
+        let circuit = ShuffleCircuit::<W> {
+            input: tuples(input),
+            shuffled: tuples(shuffled),
+        };
+        MockProver::run(5, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    /// Every value of `input` is a row of the (hardened) table `shuffled`
+    fn looks_up(input: &[u64], shuffled: &[u64]) -> bool {
+        let a = input.iter().map(|v| Value::known(Fp::from(*v))).collect();
+        let table = LookupTable::from_values(shuffled.iter().map(|v| Fp::from(*v)));
+        let circuit = MyCircuit::<Fp, AdviceLookup<true>>::with_table(a, table);
+        MockProver::run(5, &circuit, vec![]).unwrap().verify().is_ok()
+    }
+
+    #[test]
+    fn test_shuffle_vs_lookup() {
+        assert!(shuffles(&[[1], [2], [3]], &[[3], [1], [2]]));
+        assert!(looks_up(&[1, 2, 3], &[3, 1, 2]));
+
+        // duplicates: every input is in the table, but 1 is used twice and 3 never
+        assert!(!shuffles(&[[1], [1], [2]], &[[1], [2], [3]]));
+        assert!(looks_up(&[1, 1, 2], &[1, 2, 3]));
+        // the multiplicities must match on both sides
+        assert!(!shuffles(&[[1], [1], [2]], &[[1], [2], [2]]));
+
+        assert!(!shuffles(&[[1], [2], [3]], &[[1], [2], [4]]));
+        assert!(!looks_up(&[1, 2, 3], &[1, 2, 4]));
+    }
+
+    #[test]
+    fn test_shuffle_tuples() {
+        assert!(shuffles(&[[1, 2], [3, 4]], &[[3, 4], [1, 2]]));
+        // every column is the same multiset, the tuples are not
+        assert!(!shuffles(&[[1, 2], [3, 4]], &[[1, 4], [3, 2]]));
+        // an empty shuffle is trivially satisfied
+        assert!(shuffles::<2>(&[], &[]));
+    }
+
+    #[test]
+    fn test_shuffle_lengths() {
+        let circuit = ShuffleCircuit::<1> {
+            input: tuples(&[[1], [2]]),
+            shuffled: tuples(&[[1], [2], [2]]),
+        };
+        assert_matches!(MockProver::run(5, &circuit, vec![]).map(|_| ()), Err(Error::Synthesis));
+    }
+}
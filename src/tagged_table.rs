@@ -0,0 +1,274 @@
+//! Several logical lookup tables in one physical table.
+//!
+//! Every row of the physical table starts with a tag naming the logical table it belongs to
+//! (range8, range16, xor8, ...), so an input `(tag, values..)` can only match rows of its own
+//! table and new tables only add rows, not columns. Tables narrower than the physical width
+//! are padded with zero columns, and so are their inputs. Tag 0 is reserved for the all-zero
+//! row at the top of the table, the tuple every disabled row looks up.
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::*,
+    poly::Rotation,
+};
+
+use crate::lookup_padding::{usable_rows, LookupTable};
+
+/// The logical tables of a `TaggedTableChip`, tagged `1, 2, ..` in the order they are added
+#[derive(Clone, Debug, Default)]
+pub struct TableRegistry<F: FieldExt> {
+    tables: Vec<(String, LookupTable<F>)>,
+}
+
+impl<F: FieldExt> TableRegistry<F> {
+    pub fn with(mut self, name: &str, table: LookupTable<F>) -> Self {
+        assert!(self.tag(name).is_none(), "table {} is already registered", name);
+        self.tables.push((name.to_string(), table));
+        self
+    }
+
+    pub fn tag(&self, name: &str) -> Option<u64> {
+        self.tables
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|i| i as u64 + 1)
+    }
+
+    /// Widest logical table, the number of value columns the chip needs
+    pub fn width(&self) -> usize {
+        self.tables.iter().map(|(_, t)| t.width()).max().unwrap_or(0)
+    }
+
+    /// Rows of the physical table: every logical table plus the all-zero row
+    pub fn assigned_rows(&self) -> usize {
+        1 + self.tables.iter().map(|(_, t)| t.len()).sum::<usize>()
+    }
+
+    /// Fails with `NotEnoughRowsAvailable` when the physical table does not fit in `k`
+    pub fn check_fits(&self, k: u32, cs: &ConstraintSystem<F>) -> Result<(), Error> {
+        if self.assigned_rows() > usable_rows(k, cs) {
+            return Err(Error::NotEnoughRowsAvailable { current_k: k });
+        }
+        Ok(())
+    }
+}
+
+/// `{0, .., 2^bits - 1}`
+pub fn range_table<F: FieldExt>(bits: usize) -> LookupTable<F> {
+    LookupTable::from_fn(1 << bits, |i| vec![F::from(i as u64)])
+}
+
+/// `{(x, y, x ^ y) | x, y < 2^bits}`
+pub fn xor_table<F: FieldExt>(bits: usize) -> LookupTable<F> {
+    LookupTable::from_fn(1 << (2 * bits), |i| {
+        let (x, y) = ((i >> bits) as u64, (i & ((1 << bits) - 1)) as u64);
+        vec![F::from(x), F::from(y), F::from(x ^ y)]
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct TaggedTableConfig {
+    a: Vec<Column<Advice>>,
+    // the tag of the logical table each enabled row is looked up in
+    tag: Column<Fixed>,
+    s: Selector,
+    t_tag: TableColumn,
+    t: Vec<TableColumn>,
+}
+
+pub struct TaggedTableChip<F: FieldExt> {
+    config: TaggedTableConfig,
+    tables: TableRegistry<F>,
+}
+
+impl<F: FieldExt> TaggedTableChip<F> {
+    pub fn construct(config: TaggedTableConfig, tables: TableRegistry<F>) -> Self {
+        assert!(tables.width() <= config.a.len(), "a table is wider than the chip");
+        TaggedTableChip { config, tables }
+    }
+
+    /// `width` value columns, enough for the widest table the chip will be constructed with
+    pub fn configure(meta: &mut ConstraintSystem<F>, width: usize) -> TaggedTableConfig {
+        assert!(width > 0, "a lookup needs at least one column");
+
+        let a = (0..width).map(|_| meta.advice_column()).collect::<Vec<_>>();
+        let tag = meta.fixed_column();
+        let s = meta.complex_selector();
+        let t_tag = meta.lookup_table_column();
+        let t = (0..width).map(|_| meta.lookup_table_column()).collect::<Vec<_>>();
+
+        for a in a.iter() {
+            meta.enable_equality(*a);
+        }
+
+        meta.lookup("tagged lookup", |meta| {
+            let s = meta.query_selector(s);
+            let tag = meta.query_fixed(tag, Rotation::cur());
+            // a disabled row looks up the all-zero row
+            std::iter::once((s.clone() * tag, t_tag))
+                .chain(a.iter().zip(t.iter()).map(|(a, t)| {
+                    (s.clone() * meta.query_advice(*a, Rotation::cur()), *t)
+                }))
+                .collect()
+        });
+
+        TaggedTableConfig { a, tag, s, t_tag, t }
+    }
+
+    /// Loads the all-zero row followed by every registered table, once per circuit
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let width = self.config.t.len();
+        layouter.assign_table(
+            || "tagged table",
+            |mut table| {
+                let zero_row = (0, vec![F::zero(); width]);
+                let rows = self.tables.tables.iter().enumerate().flat_map(|(i, (_, t))| {
+                    t.rows().map(move |row| {
+                        let mut row = row.clone();
+                        row.resize(width, F::zero());
+                        (i as u64 + 1, row)
+                    })
+                });
+
+                for (offset, (tag, row)) in std::iter::once(zero_row).chain(rows).enumerate() {
+                    table.assign_cell(|| "tag", self.config.t_tag, offset, || Value::known(F::from(tag)))?;
+                    for (column, value) in self.config.t.iter().zip(row.iter()) {
+                        table.assign_cell(|| "value", *column, offset, || Value::known(*value))?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Looks each row of `values` up in the table called `name`, returns the assigned cells.
+    /// Fails with `Error::Synthesis` for an unknown table or a row of the wrong width.
+    pub fn lookup(
+        &self,
+        mut layouter: impl Layouter<F>,
+        name: &str,
+        values: &[Vec<Value<F>>],
+    ) -> Result<Vec<Vec<AssignedCell<F, F>>>, Error> {
+        let tag = self.tables.tag(name).ok_or(Error::Synthesis)?;
+        let table_width = self.tables.tables[tag as usize - 1].1.width();
+        if values.iter().any(|row| row.len() != table_width) {
+            return Err(Error::Synthesis);
+        }
+
+        layouter.assign_region(
+            || format!("lookup {}", name),
+            |mut region| {
+                let mut cells = vec![];
+                for (offset, row) in values.iter().enumerate() {
+                    self.config.s.enable(&mut region, offset)?;
+                    region.assign_fixed(|| "tag", self.config.tag, offset, || Value::known(F::from(tag)))?;
+
+                    let mut row_cells = vec![];
+                    for (i, column) in self.config.a.iter().enumerate() {
+                        // columns past the table width hold the zero padding of its rows
+                        let value = row.get(i).copied().unwrap_or_else(|| Value::known(F::zero()));
+                        let cell = region.assign_advice(|| "value", *column, offset, || value)?;
+                        if i < table_width {
+                            row_cells.push(cell);
+                        }
+                    }
+                    cells.push(row_cells);
+                }
+                Ok(cells)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner, dev::MockProver, halo2curves::bn256::Fr as Fp,
+    };
+
+    use super::*;
+
+    fn registry() -> TableRegistry<Fp> {
+        TableRegistry::default()
+            .with("range8", range_table(8))
+            .with("range4", range_table(4))
+            .with("xor4", xor_table(4))
+    }
+
+    struct TaggedCircuit {
+        lookups: Vec<(&'static str, Vec<Value<Fp>>)>,
+    }
+
+    impl Circuit<Fp> for TaggedCircuit {
+        type Config = TaggedTableConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            TaggedCircuit {
+                lookups: self
+                    .lookups
+                    .iter()
+                    .map(|(name, values)| (*name, vec![Value::unknown(); values.len()]))
+                    .collect(),
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            TaggedTableChip::configure(meta, 3)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let chip = TaggedTableChip::construct(config, registry());
+            chip.load_table(layouter.namespace(|| "table"))?;
+            for (name, values) in self.lookups.iter() {
+                chip.lookup(layouter.namespace(|| *name), name, &[values.clone()])?;
+            }
+            Ok(())
+        }
+    }
+
+    fn verifies(lookups: &[(&'static str, Vec<u64>)]) -> bool {
+        let circuit = TaggedCircuit {
+            lookups: lookups
+                .iter()
+                .map(|(name, values)| (*name, values.iter().map(|v| Value::known(Fp::from(*v))).collect()))
+                .collect(),
+        };
+        // 1 + 256 + 16 + 256 rows
+        let prover = MockProver::run(10, &circuit, vec![]).unwrap();
+        prover.verify().is_ok()
+    }
+
+    #[test]
+    fn test_tagged_tables() {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        TaggedCircuit::configure(&mut cs);
+        assert_eq!(registry().assigned_rows(), 529);
+        registry().check_fits(10, &cs).unwrap();
+        assert!(registry().check_fits(9, &cs).is_err());
+
+        assert!(verifies(&[
+            ("range8", vec![200]),
+            ("range4", vec![15]),
+            ("xor4", vec![3, 5, 6]),
+        ]));
+
+        // 200 is a row of range8, but not of range4
+        assert!(!verifies(&[("range4", vec![200])]));
+        // (3, 0, 0) is a zero-padded row of range4 and range8, not of xor4
+        assert!(!verifies(&[("xor4", vec![3, 0, 0])]));
+        assert!(!verifies(&[("xor4", vec![3, 5, 7])]));
+    }
+
+    #[test]
+    fn test_unknown_table() {
+        let circuit = TaggedCircuit {
+            lookups: vec![("range16", vec![Value::known(Fp::one())])],
+        };
+        assert!(matches!(
+            MockProver::run(10, &circuit, vec![]),
+            Err(Error::Synthesis)
+        ));
+    }
+}